@@ -0,0 +1,83 @@
+// Times the scanner on generated sources of a few megabytes, running
+// it in-process so that nothing but scanning is measured. If scanning
+// is linear in the size of the source, the time taken per byte should
+// stay about the same however large the input is.
+//
+// Run with `cargo bench`.
+
+use lox::Scanner;
+use std::{
+    hint,
+    time::{Duration, Instant},
+};
+
+const SIZES_IN_MB: [usize; 4] = [1, 2, 4, 8];
+
+// How much slower per byte the slowest size may be than the fastest
+// before scanning is considered to be worse than linear
+const TOLERANCE: f64 = 2.0;
+
+fn generate(size: usize) -> String {
+    let mut source = String::with_capacity(size + 1024);
+    source.push_str("fun unused() {\n");
+
+    let mut i = 0;
+    while source.len() < size {
+        source.push_str(&format!(
+            "  var v{} = \"some text\" + 12.5 * (v{} - 3); // a comment\n",
+            i + 1,
+            i
+        ));
+        i += 1;
+    }
+
+    source.push_str("}\nprint \"done\";\n");
+    source
+}
+
+fn scan(source: &str) -> Duration {
+    let start = Instant::now();
+    let mut tokens = 0;
+    for result in Scanner::new(source) {
+        assert!(result.is_ok(), "The benchmark source has a lexical error");
+        tokens += 1;
+    }
+    let elapsed = start.elapsed();
+
+    hint::black_box(tokens);
+    elapsed
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>12} {:>10} {:>10}",
+        "size", "time", "MB/s", "ns/byte"
+    );
+
+    let mut per_byte = Vec::new();
+    for &megabytes in SIZES_IN_MB.iter() {
+        let source = generate(megabytes * 1024 * 1024);
+
+        // Take the best of a few runs to smooth out noise
+        let best = (0..5).map(|_| scan(&source)).min().unwrap();
+        let nanos_per_byte = best.as_secs_f64() * 1e9 / source.len() as f64;
+        per_byte.push(nanos_per_byte);
+
+        println!(
+            "{:>6}MB {:>10.1}ms {:>10.1} {:>10.2}",
+            megabytes,
+            best.as_secs_f64() * 1000.0,
+            megabytes as f64 / best.as_secs_f64(),
+            nanos_per_byte
+        );
+    }
+
+    let fastest = per_byte.iter().cloned().fold(f64::INFINITY, f64::min);
+    let slowest = per_byte.iter().cloned().fold(0.0, f64::max);
+    assert!(
+        slowest <= fastest * TOLERANCE,
+        "Scanning isn't linear: the time per byte ranged from {:.2}ns to {:.2}ns",
+        fastest,
+        slowest
+    );
+}
@@ -0,0 +1,183 @@
+use crate::{core::Token, value::Value};
+use std::{fmt, rc::Rc};
+
+// Instructions understood by the virtual machine. Operands follow
+// the opcode in the chunk; wide operands are stored big-endian.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u8)]
+pub(crate) enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 36] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+    ];
+
+    pub(crate) fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+}
+
+// A sequence of bytecode together with the data it refers to
+#[derive(Default)]
+pub(crate) struct Chunk {
+    pub(crate) code: Vec<u8>,
+    // The source line of every byte in code
+    pub(crate) lines: Vec<usize>,
+    pub(crate) constants: Vec<Value>,
+    pub(crate) functions: Vec<Rc<FunctionProto>>,
+    // The tokens that instructions which can fail at runtime were
+    // compiled from, keyed by the offset of the instruction
+    tokens: Vec<(usize, Token)>,
+}
+
+impl Chunk {
+    pub(crate) fn new() -> Chunk {
+        Chunk {
+            code: Vec::new(),
+            lines: Vec::new(),
+            constants: Vec::new(),
+            functions: Vec::new(),
+            tokens: Vec::new(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub(crate) fn write_u16(&mut self, value: u16, line: usize) {
+        let [high, low] = value.to_be_bytes();
+        self.write(high, line);
+        self.write(low, line);
+    }
+
+    pub(crate) fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    pub(crate) fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub(crate) fn add_function(&mut self, function: FunctionProto) -> usize {
+        self.functions.push(Rc::new(function));
+        self.functions.len() - 1
+    }
+
+    pub(crate) fn add_token(&mut self, offset: usize, token: Token) {
+        self.tokens.push((offset, token));
+    }
+
+    // Finds the token that the instruction at the offset was compiled from
+    pub(crate) fn token_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens
+            .binary_search_by_key(&offset, |(token_offset, _)| *token_offset)
+            .ok()
+            .map(|index| &self.tokens[index].1)
+    }
+}
+
+// A function compiled to bytecode. The top level of a script is
+// compiled into a function without a name.
+#[derive(Default)]
+pub struct FunctionProto {
+    pub(crate) name: String,
+    pub(crate) arity: usize,
+    pub(crate) upvalue_count: usize,
+    pub(crate) chunk: Chunk,
+}
+
+impl FunctionProto {
+    pub(crate) fn new(name: &str) -> FunctionProto {
+        FunctionProto {
+            name: name.to_string(),
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::new(),
+        }
+    }
+}
+
+impl fmt::Display for FunctionProto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    core::{RuntimeError, RuntimeResult, Token},
+    function::{Callable, LoxFunction},
+    interpreter::Interpreter,
+    value::Value,
+};
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+// Methods are stored as values, so that classes can be shared by the
+// interpreter, whose methods are functions, and the virtual machine,
+// whose methods are closures
+pub struct LoxClass {
+    pub(crate) name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Value>,
+}
+
+impl LoxClass {
+    pub(crate) fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Value>,
+    ) -> LoxClass {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    // Looks the method up in this class first and then along the
+    // chain of superclasses
+    pub(crate) fn find_method(&self, name: &str) -> Option<Value> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(method.clone());
+        }
+
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+
+    // Looks up a method of a class declared in the interpreter
+    pub(crate) fn find_function(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.find_method(name) {
+            Some(Value::Function(function)) => Some(function),
+            _ => None,
+        }
+    }
+}
+
+// Calling a class creates a new instance and runs its initializer
+impl Callable for Rc<LoxClass> {
+    fn arity(&self) -> usize {
+        match self.find_function("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> RuntimeResult {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(self))));
+
+        if let Some(initializer) = self.find_function("init") {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(interpreter, arguments)?;
+        }
+
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+pub struct LoxInstance {
+    pub(crate) class: Rc<LoxClass>,
+    fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+    pub(crate) fn new(class: Rc<LoxClass>) -> LoxInstance {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // Fields shadow methods of the same name. Methods are bound to the
+    // instance they were accessed on, so that 'this' refers to it.
+    pub(crate) fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> RuntimeResult {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_function(&name.lexeme);
+        match method {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(Rc::clone(instance))))),
+            None => {
+                let message = format!("Undefined property '{}'.", name.lexeme);
+                Err(RuntimeError::new(name, &message))
+            }
+        }
+    }
+
+    pub(crate) fn get_field(&self, name: &str) -> Option<Value> {
+        self.fields.get(name).cloned()
+    }
+
+    pub(crate) fn set(&mut self, name: &str, value: Value) {
+        self.fields.insert(name.to_string(), value);
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
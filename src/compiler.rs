@@ -0,0 +1,441 @@
+use crate::{
+    chunk::{Chunk, FunctionProto, OpCode},
+    core::{FunctionDecl, Position, Stmt, Token},
+    error::Diagnostic,
+    resolver::FunctionType,
+    value::Value,
+};
+use std::{collections::HashMap, rc::Rc};
+
+struct Local {
+    name: String,
+    // None while the variable's initializer is being compiled
+    depth: Option<usize>,
+    is_captured: bool,
+}
+
+struct UpvalueRef {
+    index: u16,
+    is_local: bool,
+}
+
+// A constant that can be shared by every instruction using it.
+// Numbers are compared by their bits, so that 0 and -0 stay apart.
+#[derive(PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Number(u64),
+    Str(Rc<str>),
+}
+
+// The compilation state of a single function. Functions nest,
+// so the compiler keeps a stack of these.
+struct FunctionState {
+    function: FunctionProto,
+    function_type: FunctionType,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    // The index of every constant already in the chunk
+    constants: HashMap<ConstantKey, u16>,
+    // Set once the chunk is full, so that it is only reported once
+    too_many_constants: bool,
+}
+
+impl FunctionState {
+    fn new(name: &str, function_type: FunctionType) -> FunctionState {
+        // The first slot of every call frame holds the function being
+        // called, or the instance a method was called on
+        let slot_zero = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            _ => "",
+        };
+
+        FunctionState {
+            function: FunctionProto::new(name),
+            function_type,
+            locals: vec![Local {
+                name: slot_zero.to_string(),
+                depth: Some(0),
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            constants: HashMap::new(),
+            too_many_constants: false,
+        }
+    }
+}
+
+// Compiles a resolved syntax tree into bytecode for the virtual
+// machine. Every node emits its own instructions through the
+// helpers below. Local variables are assigned stack slots at
+// compile time, while globals are looked up by name at runtime.
+pub struct Compiler {
+    states: Vec<FunctionState>,
+    // Where the code currently being compiled came from
+    position: Position,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+impl Default for Compiler {
+    fn default() -> Compiler {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            states: Vec::new(),
+            position: Position { line: 1, column: 1 },
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // Compiles the statements into the function for a script
+    pub fn compile(
+        mut self,
+        statements: &[Box<dyn Stmt>],
+    ) -> Result<FunctionProto, Vec<Diagnostic>> {
+        self.states.push(FunctionState::new("", FunctionType::None));
+
+        for statement in statements {
+            statement.compile(&mut self);
+        }
+
+        let (function, _) = self.end_function();
+
+        if !self.diagnostics.is_empty() {
+            return Err(self.diagnostics);
+        }
+        Ok(function)
+    }
+
+    fn state(&self) -> &FunctionState {
+        self.states.last().expect("No function is being compiled")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.states
+            .last_mut()
+            .expect("No function is being compiled")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state_mut().function.chunk
+    }
+
+    // The offset at which the next instruction will be written
+    pub(crate) fn offset(&self) -> usize {
+        self.state().function.chunk.code.len()
+    }
+
+    pub(crate) fn emit_byte(&mut self, byte: u8) {
+        let line = self.position.line;
+        self.chunk().write(byte, line);
+    }
+
+    pub(crate) fn emit_u16(&mut self, value: u16) {
+        let line = self.position.line;
+        self.chunk().write_u16(value, line);
+    }
+
+    pub(crate) fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    // Emits an instruction that can fail at runtime, remembering the
+    // token it came from so that errors can point back at it
+    pub(crate) fn emit_op_for(&mut self, op: OpCode, token: &Token) {
+        self.position = token.span.start;
+        let offset = self.offset();
+        self.chunk().add_token(offset, token.clone());
+        self.emit_op(op);
+    }
+
+    pub(crate) fn emit_constant(&mut self, value: Value) {
+        let index = self.make_constant(value);
+        self.emit_op(OpCode::Constant);
+        self.emit_u16(index);
+    }
+
+    // Reuses the constant if the chunk already has it, as every
+    // literal and global name would otherwise add one
+    fn make_constant(&mut self, value: Value) -> u16 {
+        let key = match &value {
+            Value::Number(number) => Some(ConstantKey::Number(number.to_bits())),
+            Value::Str(string) => Some(ConstantKey::Str(Rc::clone(string))),
+            _ => None,
+        };
+        if let Some(index) = key.as_ref().and_then(|key| self.state().constants.get(key)) {
+            return *index;
+        }
+
+        if self.state().function.chunk.constants.len() > u16::MAX as usize {
+            if !self.state().too_many_constants {
+                self.state_mut().too_many_constants = true;
+                self.error("Too many constants in one chunk.");
+            }
+            return 0;
+        }
+
+        let index = self.chunk().add_constant(value) as u16;
+        if let Some(key) = key {
+            self.state_mut().constants.insert(key, index);
+        }
+        index
+    }
+
+    pub(crate) fn identifier_constant(&mut self, name: &str) -> u16 {
+        self.make_constant(Value::from(name))
+    }
+
+    // Emits a jump with a placeholder offset, returning where it
+    // has to be patched once the target is known
+    pub(crate) fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit_u16(u16::MAX);
+        self.offset() - 2
+    }
+
+    pub(crate) fn patch_jump(&mut self, offset: usize) {
+        let jump = self.offset() - offset - 2;
+        let jump = self.check_u16(jump, "Too much code to jump over.");
+
+        let [high, low] = jump.to_be_bytes();
+        self.chunk().code[offset] = high;
+        self.chunk().code[offset + 1] = low;
+    }
+
+    pub(crate) fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_op(OpCode::Loop);
+
+        let jump = self.offset() - loop_start + 2;
+        let jump = self.check_u16(jump, "Loop body too large.");
+        self.emit_u16(jump);
+    }
+
+    fn check_u16(&mut self, value: usize, message: &str) -> u16 {
+        if value > u16::MAX as usize {
+            self.error(message);
+            return 0;
+        }
+
+        value as u16
+    }
+
+    fn error(&mut self, message: &str) {
+        self.diagnostics
+            .push(Diagnostic::at(self.position, message));
+    }
+
+    pub(crate) fn set_position(&mut self, position: Position) {
+        self.position = position;
+    }
+
+    pub(crate) fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    // Discards the locals of the innermost scope, moving the ones
+    // captured by closures off the stack
+    pub(crate) fn end_scope(&mut self) {
+        self.state_mut().scope_depth -= 1;
+
+        loop {
+            let state = self.state();
+            let captured = match state.locals.last() {
+                Some(local) if local.depth.is_none_or(|depth| depth > state.scope_depth) => {
+                    local.is_captured
+                }
+                _ => break,
+            };
+
+            if captured {
+                self.emit_op(OpCode::CloseUpvalue);
+            } else {
+                self.emit_op(OpCode::Pop);
+            }
+            self.state_mut().locals.pop();
+        }
+    }
+
+    pub(crate) fn add_local(&mut self, name: &str) {
+        if self.state().locals.len() > u16::MAX as usize {
+            self.error("Too many local variables in function.");
+            return;
+        }
+
+        self.state_mut().locals.push(Local {
+            name: name.to_string(),
+            depth: None,
+            is_captured: false,
+        });
+    }
+
+    pub(crate) fn is_global_scope(&self) -> bool {
+        self.state().scope_depth == 0
+    }
+
+    // Globals are late bound, so only locals have to be declared
+    pub(crate) fn declare_variable(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            self.add_local(&name.lexeme);
+        }
+    }
+
+    // Makes a declared variable available, once its value is on the stack
+    pub(crate) fn define_variable(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+
+        self.define_global(name);
+    }
+
+    pub(crate) fn define_global(&mut self, name: &Token) {
+        let global = self.identifier_constant(&name.lexeme);
+        self.emit_op(OpCode::DefineGlobal);
+        self.emit_u16(global);
+    }
+
+    pub(crate) fn mark_initialized(&mut self) {
+        let state = self.state_mut();
+        let depth = state.scope_depth;
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(depth);
+        }
+    }
+
+    pub(crate) fn get_variable(&mut self, name: &Token) {
+        let current = self.states.len() - 1;
+
+        if let Some(slot) = self.resolve_local(current, &name.lexeme) {
+            self.position = name.span.start;
+            self.emit_op(OpCode::GetLocal);
+            self.emit_u16(slot);
+        } else if let Some(index) = self.resolve_upvalue(current, &name.lexeme) {
+            self.position = name.span.start;
+            self.emit_op(OpCode::GetUpvalue);
+            self.emit_u16(index);
+        } else {
+            let global = self.identifier_constant(&name.lexeme);
+            self.emit_op_for(OpCode::GetGlobal, name);
+            self.emit_u16(global);
+        }
+    }
+
+    pub(crate) fn set_variable(&mut self, name: &Token) {
+        let current = self.states.len() - 1;
+
+        if let Some(slot) = self.resolve_local(current, &name.lexeme) {
+            self.position = name.span.start;
+            self.emit_op(OpCode::SetLocal);
+            self.emit_u16(slot);
+        } else if let Some(index) = self.resolve_upvalue(current, &name.lexeme) {
+            self.position = name.span.start;
+            self.emit_op(OpCode::SetUpvalue);
+            self.emit_u16(index);
+        } else {
+            let global = self.identifier_constant(&name.lexeme);
+            self.emit_op_for(OpCode::SetGlobal, name);
+            self.emit_u16(global);
+        }
+    }
+
+    fn resolve_local(&self, state: usize, name: &str) -> Option<u16> {
+        self.states[state]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u16)
+    }
+
+    // Looks for the variable in the functions enclosing the given one,
+    // threading it through an upvalue in each function in between
+    fn resolve_upvalue(&mut self, state: usize, name: &str) -> Option<u16> {
+        if state == 0 {
+            return None;
+        }
+
+        if let Some(slot) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(state, slot, true));
+        }
+
+        if let Some(index) = self.resolve_upvalue(state - 1, name) {
+            return Some(self.add_upvalue(state, index, false));
+        }
+
+        None
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u16, is_local: bool) -> u16 {
+        let state = &mut self.states[state];
+
+        if let Some(existing) = state
+            .upvalues
+            .iter()
+            .position(|upvalue| upvalue.index == index && upvalue.is_local == is_local)
+        {
+            return existing as u16;
+        }
+
+        state.upvalues.push(UpvalueRef { index, is_local });
+        state.function.upvalue_count = state.upvalues.len();
+        (state.upvalues.len() - 1) as u16
+    }
+
+    pub(crate) fn function_type(&self) -> FunctionType {
+        self.state().function_type
+    }
+
+    // Compiles the function and emits the instruction creating a closure of it
+    pub(crate) fn function(&mut self, declaration: &FunctionDecl, function_type: FunctionType) {
+        self.position = declaration.name.span.start;
+        self.states
+            .push(FunctionState::new(&declaration.name.lexeme, function_type));
+        self.begin_scope();
+
+        for param in &declaration.params {
+            self.state_mut().function.arity += 1;
+            self.declare_variable(param);
+            self.define_variable(param);
+        }
+
+        for statement in &declaration.body {
+            statement.compile(self);
+        }
+
+        let (function, upvalues) = self.end_function();
+        let index = self.chunk().add_function(function);
+        let index = self.check_u16(index, "Too many functions in one chunk.");
+
+        self.emit_op(OpCode::Closure);
+        self.emit_u16(index);
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_u16(upvalue.index);
+        }
+    }
+
+    // Initializers implicitly return the instance they were called on
+    pub(crate) fn emit_return(&mut self) {
+        if self.function_type() == FunctionType::Initializer {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_u16(0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+
+        self.emit_op(OpCode::Return);
+    }
+
+    fn end_function(&mut self) -> (FunctionProto, Vec<UpvalueRef>) {
+        self.emit_return();
+
+        let state = self.states.pop().expect("No function is being compiled");
+        (state.function, state.upvalues)
+    }
+}
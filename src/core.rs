@@ -1,6 +1,23 @@
-use std::any;
-use std::fmt;
+use crate::{
+    chunk::OpCode,
+    class::{LoxClass, LoxInstance},
+    compiler::Compiler,
+    environment::Environment,
+    error::Diagnostic,
+    function::LoxFunction,
+    interpreter::Interpreter,
+    resolver::{ClassType, FunctionType, Resolver},
+    value::Value,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    error, fmt,
+    io::Write,
+    rc::Rc,
+};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, PartialEq, Debug)]
 pub enum TokenType {
     LeftParen,
@@ -44,19 +61,44 @@ pub enum TokenType {
     EOF,
 }
 
+// A place in the source. Lines and columns are counted from 1,
+// with columns counted in characters.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// The part of the source a token was scanned from. The offset and
+// length are in bytes, and the end is the position just after the
+// last character of the token.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Span {
+    pub offset: usize,
+    pub len: usize,
+    pub start: Position,
+    pub end: Position,
+}
+
 #[derive(Clone, Debug)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: usize) -> Token {
+    pub fn new(token_type: TokenType, lexeme: String, span: Span) -> Token {
         Token {
             token_type,
             lexeme,
-            line,
+            span,
         }
     }
 
@@ -64,9 +106,13 @@ impl Token {
         Token {
             token_type: TokenType::EOF,
             lexeme: String::new(),
-            line: 0,
+            span: Span::default(),
         }
     }
+
+    pub fn line(&self) -> usize {
+        self.span.start.line
+    }
 }
 
 impl fmt::Display for Token {
@@ -77,28 +123,212 @@ impl fmt::Display for Token {
 
 pub struct NextTokenInfo(pub char, pub TokenType, pub TokenType);
 
-type LoxObject = Box<dyn any::Any>;
-
 pub trait Expr: fmt::Display {
-    fn interpret(&self) -> RuntimeResult;
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult;
+    fn resolve(&self, resolver: &mut Resolver);
+    fn compile(&self, compiler: &mut Compiler);
+
+    // Turns an expression found on the left side of an '='
+    // into the matching assignment. Returns None when the
+    // expression is not a valid assignment target.
+    fn assign(self: Box<Self>, _value: Box<dyn Expr>) -> Option<Box<dyn Expr>> {
+        None
+    }
+}
+
+pub trait Stmt: fmt::Display {
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult;
+    fn resolve(&self, resolver: &mut Resolver);
+    fn compile(&self, compiler: &mut Compiler);
+}
+
+// A problem found while scanning, such as an unexpected character
+#[derive(Debug)]
+pub struct LexError {
+    pub message: String,
+    pub span: Span,
+    pub label: Option<String>,
+    pub help: Option<String>,
+}
+
+impl LexError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(self.span, &self.message);
+        if let Some(label) = &self.label {
+            diagnostic = diagnostic.with_label(label);
+        }
+        if let Some(help) = &self.help {
+            diagnostic = diagnostic.with_help(help);
+        }
+        diagnostic
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span.start, self.message)
+    }
+}
+
+impl error::Error for LexError {}
+
+// A syntax error that stops the statement being parsed. The
+// diagnostic is boxed to keep results carrying the error small.
+#[derive(Debug)]
+pub struct ParseError {
+    pub diagnostic: Box<Diagnostic>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diagnostic)
+    }
+}
+
+impl error::Error for ParseError {}
+
+// The most calls that can be in progress at once, counting the
+// script itself. Going deeper is reported as a stack overflow.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+// The stack a thread needs for programs to recurse as deep as the
+// limit, as the tree-walker makes every call on the Rust stack. A
+// call took up to 15 KiB in a debug build when nested inside a few
+// expressions, and about a fifth of that in a release build.
+pub const STACK_SIZE: usize = MAX_CALL_DEPTH * 32 * 1024;
+
+// An error raised while a program is running. The trace lists the
+// calls that were being run when it happened, innermost first.
+#[derive(Debug)]
+pub struct RuntimeError {
+    // Missing when the error didn't happen in the source, such as
+    // when the host calls something that isn't a function
+    pub span: Option<Span>,
+    pub message: String,
+    pub trace: Vec<TraceFrame>,
+    // The line being run in the call that is added to the trace next
+    line: usize,
 }
 
-pub struct SyntaxError {}
-pub struct ParseError {}
-pub struct RuntimeError(pub Token);
+// A call that was being run when a runtime error happened.
+// Top-level code is run by a call to a function without a name.
+#[derive(Debug)]
+pub struct TraceFrame {
+    pub function: String,
+    pub line: usize,
+}
 
 impl RuntimeError {
-    pub fn empty(token_type: TokenType) -> Self {
-        let mut empty_token = Token::empty();
-        empty_token.token_type = token_type;
+    pub fn new(token: &Token, message: &str) -> RuntimeError {
+        RuntimeError {
+            span: Some(token.span),
+            message: message.to_string(),
+            trace: Vec::new(),
+            line: token.line(),
+        }
+    }
+
+    pub fn without_span(message: &str) -> RuntimeError {
+        RuntimeError {
+            span: None,
+            message: message.to_string(),
+            trace: Vec::new(),
+            line: 0,
+        }
+    }
+
+    // Records that the error unwound out of a call to the function
+    pub fn add_frame(&mut self, function: &str) {
+        self.trace.push(TraceFrame {
+            function: function.to_string(),
+            line: self.line,
+        });
+    }
+
+    // Sets the line of the call that the error is unwinding into
+    pub fn set_line(&mut self, line: usize) {
+        self.line = line;
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = match self.span {
+            Some(span) => Diagnostic::new(span, &self.message),
+            None => Diagnostic::without_span(&self.message),
+        };
+        diagnostic.with_trace(&self.trace)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{}: {}", span.start, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl error::Error for RuntimeError {}
 
-        RuntimeError(empty_token)
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.function.is_empty() {
+            write!(f, "[line {}] in script", self.line)
+        } else {
+            write!(f, "[line {}] in {}()", self.line, self.function)
+        }
     }
 }
 
-pub type SyntaxResult = Result<(), SyntaxError>;
+pub type LexResult = Result<Token, LexError>;
 pub type ParseResult = Result<Box<dyn Expr>, ParseError>;
-pub type RuntimeResult = Result<LoxObject, RuntimeError>;
+pub type StmtResult = Result<Box<dyn Stmt>, ParseError>;
+pub type RuntimeResult = Result<Value, RuntimeError>;
+pub type ExecResult = Result<(), Unwind>;
+
+// Stops the execution of statements before they have all run,
+// either to return a value from a function or because of an error
+pub enum Unwind {
+    Return(Value),
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+// The parts of a function declaration shared between the
+// syntax tree and every function value created from it
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Box<dyn Stmt>>,
+}
+
+impl FunctionDecl {
+    pub fn new(name: Token, params: Vec<Token>, body: Vec<Box<dyn Stmt>>) -> FunctionDecl {
+        FunctionDecl { name, params, body }
+    }
+}
+
+impl fmt::Display for FunctionDecl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (", self.name)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        write!(f, ")")?;
+        for statement in &self.body {
+            write!(f, " {}", statement)?;
+        }
+        Ok(())
+    }
+}
 
 // Creates an generic Expression Type for a given set of fields
 //
@@ -118,7 +348,9 @@ pub type RuntimeResult = Result<LoxObject, RuntimeError>;
 // Parameters:
 //
 // $trt: The Expr trait which each new type must
-// implement
+// implement. Statements are created with the same
+// macro, in which case $trt bounds the Expression
+// types they hold
 //
 // $e: Identifier of the new struct
 //
@@ -127,30 +359,40 @@ pub type RuntimeResult = Result<LoxObject, RuntimeError>;
 //
 // $($field:ident: $ty:ident),*: The sequence of
 // fields and their types that will populate the struct
+//
+// $($extra:ident: $xty:ty),*: An optional sequence of
+// fields, separated from the others by a semicolon,
+// that are filled in by later passes over the tree.
+// They are not passed to new() and start out with
+// their default values.
 
 macro_rules! expr {
-    ($trt:ident: $e:ident<$($T:ident),+> => $($field:ident: $ty:ty),*) => {
+    ($trt:ident: $e:ident<$($T:ident),+> => $($field:ident: $ty:ty),* $(; $($extra:ident: $xty:ty),*)?) => {
         pub struct $e<$($T: $trt + ?Sized,)+> {
             $(pub $field: $ty,)*
+            $($(pub $extra: $xty,)*)?
         }
 
         impl<$($T,)+> $e<$($T,)+> where $($T: $trt + ?Sized,)+ {
             pub fn new($($field: $ty,)*) -> Self {
                 Self {
                     $($field,)*
+                    $($($extra: Default::default(),)*)?
                 }
             }
         }
     };
-    ($trt:ident: $e:ident => $($field:ident: $ty:ident),*) => {
+    ($trt:ident: $e:ident => $($field:ident: $ty:ty),* $(; $($extra:ident: $xty:ty),*)?) => {
         pub struct $e {
             $(pub $field: $ty,)*
+            $($(pub $extra: $xty,)*)?
         }
 
         impl $e {
             pub fn new($($field: $ty,)*) -> Self {
                 Self {
                     $($field,)*
+                    $($($extra: Default::default(),)*)?
                 }
             }
         }
@@ -164,104 +406,75 @@ where
     T: Expr + ?Sized,
     U: Expr + ?Sized,
 {
-    fn interpret(&self) -> RuntimeResult {
-        let left_object = self.left.interpret()?;
-        let right_object = self.right.interpret()?;
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        let left_value = self.left.interpret(interpreter)?;
+        let right_value = self.right.interpret(interpreter)?;
 
         use TokenType::*;
         match self.op.token_type {
-            Plus | Minus | Slash | Star | Greater | GreaterEqual | Less | LessEqual => {
-                if let Plus = self.op.token_type {
-                    let lvalue = left_object.downcast_ref::<String>();
-                    let rvalue = right_object.downcast_ref::<String>();
-
-                    if lvalue.is_some() && rvalue.is_some() {
-                        let str1 = lvalue.unwrap();
-                        let str2 = rvalue.unwrap();
-                        let mut new_string = String::with_capacity(str1.len() + str2.len());
-                        new_string.push_str(str1);
-                        new_string.push_str(str2);
-
-                        return Ok(Box::new(new_string));
-                    }
-                }
+            EqualEqual => return Ok(Value::Bool(left_value == right_value)),
+            BangEqual => return Ok(Value::Bool(left_value != right_value)),
+            _ => (),
+        }
 
-                let lvalue = left_object.downcast_ref::<f64>();
-                let rvalue = right_object.downcast_ref::<f64>();
+        if let (Plus, Value::Str(str1), Value::Str(str2)) =
+            (&self.op.token_type, &left_value, &right_value)
+        {
+            let mut new_string = String::with_capacity(str1.len() + str2.len());
+            new_string.push_str(str1);
+            new_string.push_str(str2);
 
-                if lvalue.is_none() || rvalue.is_none() {
-                    return Err(RuntimeError(self.op.clone()));
-                }
+            return Ok(Value::from(new_string));
+        }
 
-                let lvalue = lvalue.unwrap();
-                let rvalue = rvalue.unwrap();
-
-                match self.op.token_type {
-                    Plus => Ok(Box::new(*lvalue + *rvalue)),
-                    Minus => Ok(Box::new(*lvalue - *rvalue)),
-                    Slash => Ok(Box::new(*lvalue / *rvalue)),
-                    Star => Ok(Box::new(*lvalue * *rvalue)),
-                    Greater => Ok(Box::new(*lvalue > *rvalue)),
-                    GreaterEqual => Ok(Box::new(*lvalue >= *rvalue)),
-                    Less => Ok(Box::new(*lvalue < *rvalue)),
-                    LessEqual => Ok(Box::new(*lvalue <= *rvalue)),
-                    _ => Err(RuntimeError(self.op.clone())),
-                }
+        let (lvalue, rvalue) = match (left_value, right_value) {
+            (Value::Number(lvalue), Value::Number(rvalue)) => (lvalue, rvalue),
+            _ if self.op.token_type == Plus => {
+                return Err(RuntimeError::new(
+                    &self.op,
+                    "Operands must be two numbers or two strings.",
+                ))
             }
-            EqualEqual | BangEqual => {
-                let mut ans = false;
-
-                let lvalue = left_object.downcast_ref::<Option<bool>>();
-                let rvalue = right_object.downcast_ref::<Option<bool>>();
-
-                if lvalue.is_some() && rvalue.is_some() {
-                    let lvalue = lvalue.unwrap();
-                    let rvalue = rvalue.unwrap();
-
-                    ans = match *lvalue {
-                        None => match *rvalue {
-                            None => true,
-                            _ => ans,
-                        },
-                        Some(true) => match *rvalue {
-                            Some(true) => true,
-                            _ => ans,
-                        },
-                        Some(false) => match *rvalue {
-                            Some(false) => true,
-                            _ => ans,
-                        },
-                    };
-                }
-
-                let lvalue = left_object.downcast_ref::<String>();
-                let rvalue = right_object.downcast_ref::<String>();
-
-                if lvalue.is_some() && rvalue.is_some() {
-                    let str1 = lvalue.unwrap();
-                    let str2 = rvalue.unwrap();
+            _ => return Err(RuntimeError::new(&self.op, "Operands must be numbers.")),
+        };
 
-                    ans = str1 == str2;
-                }
-
-                let lvalue = left_object.downcast_ref::<f64>();
-                let rvalue = right_object.downcast_ref::<f64>();
+        match self.op.token_type {
+            Plus => Ok(Value::Number(lvalue + rvalue)),
+            Minus => Ok(Value::Number(lvalue - rvalue)),
+            Slash => Ok(Value::Number(lvalue / rvalue)),
+            Star => Ok(Value::Number(lvalue * rvalue)),
+            Greater => Ok(Value::Bool(lvalue > rvalue)),
+            GreaterEqual => Ok(Value::Bool(lvalue >= rvalue)),
+            Less => Ok(Value::Bool(lvalue < rvalue)),
+            LessEqual => Ok(Value::Bool(lvalue <= rvalue)),
+            _ => unreachable!("Invalid binary operator"),
+        }
+    }
 
-                if lvalue.is_some() && rvalue.is_some() {
-                    let num1 = lvalue.unwrap();
-                    let num2 = rvalue.unwrap();
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.left.resolve(resolver);
+        self.right.resolve(resolver);
+    }
 
-                    ans = num1 == num2;
-                }
+    fn compile(&self, compiler: &mut Compiler) {
+        self.left.compile(compiler);
+        self.right.compile(compiler);
 
-                if self.op.token_type == EqualEqual {
-                    Ok(Box::new(ans))
-                } else {
-                    Ok(Box::new(!ans))
-                }
-            }
-            _ => Err(RuntimeError(self.op.clone())),
-        }
+        use TokenType::*;
+        let op = match self.op.token_type {
+            EqualEqual => OpCode::Equal,
+            BangEqual => OpCode::NotEqual,
+            Greater => OpCode::Greater,
+            GreaterEqual => OpCode::GreaterEqual,
+            Less => OpCode::Less,
+            LessEqual => OpCode::LessEqual,
+            Plus => OpCode::Add,
+            Minus => OpCode::Subtract,
+            Star => OpCode::Multiply,
+            Slash => OpCode::Divide,
+            _ => unreachable!("Invalid binary operator"),
+        };
+        compiler.emit_op_for(op, &self.op);
     }
 }
 
@@ -281,8 +494,16 @@ impl<T> Expr for Grouping<T>
 where
     T: Expr + ?Sized,
 {
-    fn interpret(&self) -> RuntimeResult {
-        return Ok(Box::new(self.expression.interpret()?));
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        self.expression.interpret(interpreter)
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.expression.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.expression.compile(compiler);
     }
 }
 
@@ -298,26 +519,26 @@ where
 expr!(Expr: Literal => value: TokenType);
 
 impl Expr for Literal {
-    fn interpret(&self) -> RuntimeResult {
-        if let TokenType::Number(num) = self.value {
-            return Ok(Box::new(num));
-        }
-        if let TokenType::Str(string) = self.value.clone() {
-            return Ok(Box::new(string));
+    fn interpret(&self, _interpreter: &mut Interpreter) -> RuntimeResult {
+        match &self.value {
+            TokenType::Number(num) => Ok(Value::Number(*num)),
+            TokenType::Str(string) => Ok(Value::from(string.as_str())),
+            TokenType::True => Ok(Value::Bool(true)),
+            TokenType::False => Ok(Value::Bool(false)),
+            TokenType::Nil => Ok(Value::Nil),
+            _ => unreachable!("Invalid literal"),
         }
+    }
 
-        match self.value {
-            TokenType::True | TokenType::False | TokenType::Nil => {
-                let mut option: Option<bool> = None;
-                if self.value == TokenType::True {
-                    option = Some(true);
-                } else if self.value == TokenType::False {
-                    option = Some(false);
-                }
+    fn resolve(&self, _resolver: &mut Resolver) {}
 
-                Ok(Box::new(option))
-            }
-            _ => Err(RuntimeError::empty(self.value.clone())),
+    fn compile(&self, compiler: &mut Compiler) {
+        match &self.value {
+            TokenType::Number(num) => compiler.emit_constant(Value::Number(*num)),
+            TokenType::Str(string) => compiler.emit_constant(Value::from(string.as_str())),
+            TokenType::True => compiler.emit_op(OpCode::True),
+            TokenType::False => compiler.emit_op(OpCode::False),
+            _ => compiler.emit_op(OpCode::Nil),
         }
     }
 }
@@ -339,18 +560,28 @@ impl<T> Expr for Unary<T>
 where
     T: Expr + ?Sized,
 {
-    fn interpret(&self) -> RuntimeResult {
-        let right_object = self.right.interpret()?;
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        let right_value = self.right.interpret(interpreter)?;
         match self.op.token_type {
-            TokenType::Minus => match right_object.downcast::<f64>() {
-                Ok(num) => Ok(Box::new(-1.0 * *num)),
-                _ => Err(RuntimeError(self.op.clone())),
+            TokenType::Minus => match right_value {
+                Value::Number(num) => Ok(Value::Number(-num)),
+                _ => Err(RuntimeError::new(&self.op, "Operand must be a number.")),
             },
-            TokenType::Bang => match right_object.downcast::<bool>() {
-                Ok(truth) => Ok(Box::new(truth)),
-                _ => Err(RuntimeError(self.op.clone())),
-            },
-            _ => Err(RuntimeError(self.op.clone())),
+            TokenType::Bang => Ok(Value::Bool(!right_value.is_truthy())),
+            _ => unreachable!("Invalid unary operator"),
+        }
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.right.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.right.compile(compiler);
+
+        match self.op.token_type {
+            TokenType::Minus => compiler.emit_op_for(OpCode::Negate, &self.op),
+            _ => compiler.emit_op(OpCode::Not),
         }
     }
 }
@@ -363,3 +594,881 @@ where
         write!(f, "({} {})", self.op, self.right)
     }
 }
+
+expr!(Expr: Variable => name: Token; depth: Cell<Option<usize>>);
+
+impl Expr for Variable {
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        interpreter.lookup_variable(&self.name, self.depth.get())
+    }
+
+    fn assign(self: Box<Self>, value: Box<dyn Expr>) -> Option<Box<dyn Expr>> {
+        Some(Box::new(Assign::new(self.name, value)))
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        if resolver.is_declared_only(&self.name) {
+            resolver.report(
+                Diagnostic::new(
+                    self.name.span,
+                    "Can't read local variable in its own initializer.",
+                )
+                .with_note("the variable is not defined until its initializer has run"),
+            );
+        }
+
+        resolver.resolve_local(&self.depth, &self.name.lexeme);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        compiler.get_variable(&self.name);
+    }
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+expr!(Expr: Assign<T> => name: Token, value: Box<T>; depth: Cell<Option<usize>>);
+
+impl<T> Expr for Assign<T>
+where
+    T: Expr + ?Sized,
+{
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        let value = self.value.interpret(interpreter)?;
+        interpreter.assign_variable(&self.name, self.depth.get(), value.clone())?;
+
+        Ok(value)
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.value.resolve(resolver);
+        resolver.resolve_local(&self.depth, &self.name.lexeme);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.value.compile(compiler);
+        compiler.set_variable(&self.name);
+    }
+}
+
+impl<T> fmt::Display for Assign<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(= {} {})", self.name, self.value)
+    }
+}
+
+expr!(Expr: Logical<T, U> => left: Box<T>, op: Token, right: Box<U>);
+
+impl<T, U> Expr for Logical<T, U>
+where
+    T: Expr + ?Sized,
+    U: Expr + ?Sized,
+{
+    // Short-circuits, returning the operand that decided the result
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        let left_value = self.left.interpret(interpreter)?;
+
+        if self.op.token_type == TokenType::Or {
+            if left_value.is_truthy() {
+                return Ok(left_value);
+            }
+        } else if !left_value.is_truthy() {
+            return Ok(left_value);
+        }
+
+        self.right.interpret(interpreter)
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.left.resolve(resolver);
+        self.right.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.left.compile(compiler);
+        compiler.set_position(self.op.span.start);
+
+        // The left operand is left on the stack as the result
+        // when it decides the outcome
+        if self.op.token_type == TokenType::Or {
+            let else_jump = compiler.emit_jump(OpCode::JumpIfFalse);
+            let end_jump = compiler.emit_jump(OpCode::Jump);
+
+            compiler.patch_jump(else_jump);
+            compiler.emit_op(OpCode::Pop);
+            self.right.compile(compiler);
+            compiler.patch_jump(end_jump);
+        } else {
+            let end_jump = compiler.emit_jump(OpCode::JumpIfFalse);
+
+            compiler.emit_op(OpCode::Pop);
+            self.right.compile(compiler);
+            compiler.patch_jump(end_jump);
+        }
+    }
+}
+
+impl<T, U> fmt::Display for Logical<T, U>
+where
+    T: Expr + ?Sized,
+    U: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} {} {})", self.op, self.left, self.right)
+    }
+}
+
+expr!(Expr: Call<T> => callee: Box<T>, paren: Token, arguments: Vec<Box<dyn Expr>>);
+
+impl<T> Expr for Call<T>
+where
+    T: Expr + ?Sized,
+{
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        let callee = self.callee.interpret(interpreter)?;
+
+        let mut arguments = Vec::with_capacity(self.arguments.len());
+        for argument in &self.arguments {
+            arguments.push(argument.interpret(interpreter)?);
+        }
+
+        // An error from inside the call continues in the
+        // trace on the line of this call
+        interpreter
+            .call(&callee, arguments, Some(&self.paren))
+            .map_err(|mut error| {
+                error.set_line(self.paren.line());
+                error
+            })
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.callee.resolve(resolver);
+        for argument in &self.arguments {
+            argument.resolve(resolver);
+        }
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.callee.compile(compiler);
+        for argument in &self.arguments {
+            argument.compile(compiler);
+        }
+
+        compiler.emit_op_for(OpCode::Call, &self.paren);
+        compiler.emit_byte(self.arguments.len() as u8);
+    }
+}
+
+impl<T> fmt::Display for Call<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(call {}", self.callee)?;
+        for argument in &self.arguments {
+            write!(f, " {}", argument)?;
+        }
+        write!(f, ")")
+    }
+}
+
+expr!(Expr: Get<T> => object: Box<T>, name: Token);
+
+impl<T> Expr for Get<T>
+where
+    T: Expr + ?Sized + 'static,
+{
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        match self.object.interpret(interpreter)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, &self.name),
+            _ => Err(RuntimeError::new(
+                &self.name,
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn assign(self: Box<Self>, value: Box<dyn Expr>) -> Option<Box<dyn Expr>> {
+        let get = *self;
+        Some(Box::new(Set::new(get.object, get.name, value)))
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.object.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.object.compile(compiler);
+
+        let name = compiler.identifier_constant(&self.name.lexeme);
+        compiler.emit_op_for(OpCode::GetProperty, &self.name);
+        compiler.emit_u16(name);
+    }
+}
+
+impl<T> fmt::Display for Get<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(. {} {})", self.object, self.name)
+    }
+}
+
+expr!(Expr: Set<T, U> => object: Box<T>, name: Token, value: Box<U>);
+
+impl<T, U> Expr for Set<T, U>
+where
+    T: Expr + ?Sized,
+    U: Expr + ?Sized,
+{
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        // Like the operands of a binary expression, both sides
+        // are evaluated before the type of the object is checked
+        let object = self.object.interpret(interpreter)?;
+        let value = self.value.interpret(interpreter)?;
+
+        let instance = match object {
+            Value::Instance(instance) => instance,
+            _ => return Err(RuntimeError::new(&self.name, "Only instances have fields.")),
+        };
+        instance.borrow_mut().set(&self.name.lexeme, value.clone());
+
+        Ok(value)
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.value.resolve(resolver);
+        self.object.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.object.compile(compiler);
+        self.value.compile(compiler);
+
+        let name = compiler.identifier_constant(&self.name.lexeme);
+        compiler.emit_op_for(OpCode::SetProperty, &self.name);
+        compiler.emit_u16(name);
+    }
+}
+
+impl<T, U> fmt::Display for Set<T, U>
+where
+    T: Expr + ?Sized,
+    U: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(= (. {} {}) {})", self.object, self.name, self.value)
+    }
+}
+
+expr!(Expr: This => keyword: Token; depth: Cell<Option<usize>>);
+
+impl Expr for This {
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        interpreter.lookup_variable(&self.keyword, self.depth.get())
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        if resolver.current_class == ClassType::None {
+            resolver.error(&self.keyword, "Can't use 'this' outside of a class.");
+            return;
+        }
+
+        resolver.resolve_local(&self.depth, "this");
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        compiler.get_variable(&self.keyword);
+    }
+}
+
+impl fmt::Display for This {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "this")
+    }
+}
+
+expr!(Expr: Super => keyword: Token, method: Token; depth: Cell<Option<usize>>);
+
+impl Expr for Super {
+    // Looks the method up on the superclass of the class containing the
+    // call, binding it to the instance the current method was called on
+    fn interpret(&self, interpreter: &mut Interpreter) -> RuntimeResult {
+        // 'this' is always bound in the scope just inside the one binding 'super'
+        let distance = self.depth.get().unwrap_or(0);
+        let environment = interpreter.environment.borrow();
+
+        let superclass = match environment.get_at(distance, "super") {
+            Value::Class(superclass) => superclass,
+            _ => unreachable!("'super' is not bound to a class"),
+        };
+
+        let instance = match environment.get_at(distance.saturating_sub(1), "this") {
+            Value::Instance(instance) => instance,
+            _ => unreachable!("'this' is not bound to an instance"),
+        };
+
+        match superclass.find_function(&self.method.lexeme) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+            None => {
+                let message = format!("Undefined property '{}'.", self.method.lexeme);
+                Err(RuntimeError::new(&self.method, &message))
+            }
+        }
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        match resolver.current_class {
+            ClassType::None => {
+                resolver.error(&self.keyword, "Can't use 'super' outside of a class.")
+            }
+            ClassType::Class => resolver.report(
+                Diagnostic::new(
+                    self.keyword.span,
+                    "Can't use 'super' in a class with no superclass.",
+                )
+                .with_help("declare a superclass with 'class Name < Superclass'"),
+            ),
+            ClassType::Subclass => (),
+        }
+
+        resolver.resolve_local(&self.depth, "super");
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        let this = Token::new(TokenType::This, "this".to_string(), self.keyword.span);
+        compiler.get_variable(&this);
+        compiler.get_variable(&self.keyword);
+
+        let method = compiler.identifier_constant(&self.method.lexeme);
+        compiler.emit_op_for(OpCode::GetSuper, &self.method);
+        compiler.emit_u16(method);
+    }
+}
+
+impl fmt::Display for Super {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(super {})", self.method)
+    }
+}
+
+expr!(Expr: Expression<T> => expression: Box<T>);
+
+impl<T> Stmt for Expression<T>
+where
+    T: Expr + ?Sized,
+{
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        self.expression.interpret(interpreter)?;
+        Ok(())
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.expression.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.expression.compile(compiler);
+        compiler.emit_op(OpCode::Pop);
+    }
+}
+
+impl<T> fmt::Display for Expression<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(; {})", self.expression)
+    }
+}
+
+expr!(Expr: Print<T> => expression: Box<T>);
+
+impl<T> Stmt for Print<T>
+where
+    T: Expr + ?Sized,
+{
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        let value = self.expression.interpret(interpreter)?;
+        writeln!(interpreter.output, "{}", value).map_err(|error| {
+            let message = format!("Couldn't write the output: {}.", error);
+            RuntimeError::without_span(&message)
+        })?;
+        Ok(())
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.expression.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.expression.compile(compiler);
+        compiler.emit_op(OpCode::Print);
+    }
+}
+
+impl<T> fmt::Display for Print<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(print {})", self.expression)
+    }
+}
+
+expr!(Expr: Var<T> => name: Token, initializer: Option<Box<T>>);
+
+impl<T> Stmt for Var<T>
+where
+    T: Expr + ?Sized,
+{
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        let value = match &self.initializer {
+            Some(initializer) => initializer.interpret(interpreter)?,
+            None => Value::Nil,
+        };
+
+        interpreter
+            .environment
+            .borrow_mut()
+            .define(&self.name.lexeme, value);
+        Ok(())
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        resolver.declare(&self.name);
+        if let Some(initializer) = &self.initializer {
+            initializer.resolve(resolver);
+        }
+        resolver.define(&self.name);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        compiler.declare_variable(&self.name);
+
+        match &self.initializer {
+            Some(initializer) => initializer.compile(compiler),
+            None => {
+                compiler.set_position(self.name.span.start);
+                compiler.emit_op(OpCode::Nil);
+            }
+        }
+
+        compiler.define_variable(&self.name);
+    }
+}
+
+impl<T> fmt::Display for Var<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.initializer {
+            Some(initializer) => write!(f, "(var {} {})", self.name, initializer),
+            None => write!(f, "(var {})", self.name),
+        }
+    }
+}
+
+expr!(Expr: Block => statements: Vec<Box<dyn Stmt>>);
+
+impl Stmt for Block {
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        let environment = Environment::with_enclosing(Rc::clone(&interpreter.environment));
+        interpreter.execute_block(&self.statements, environment)
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        resolver.begin_scope();
+        resolver.resolve(&self.statements);
+        resolver.end_scope();
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        compiler.begin_scope();
+        for statement in &self.statements {
+            statement.compile(compiler);
+        }
+        compiler.end_scope();
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(block")?;
+        for statement in &self.statements {
+            write!(f, " {}", statement)?;
+        }
+        write!(f, ")")
+    }
+}
+
+expr!(Expr: If<T> => condition: Box<T>, then_branch: Box<dyn Stmt>, else_branch: Option<Box<dyn Stmt>>);
+
+impl<T> Stmt for If<T>
+where
+    T: Expr + ?Sized,
+{
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        if self.condition.interpret(interpreter)?.is_truthy() {
+            self.then_branch.execute(interpreter)
+        } else if let Some(else_branch) = &self.else_branch {
+            else_branch.execute(interpreter)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.condition.resolve(resolver);
+        self.then_branch.resolve(resolver);
+        if let Some(else_branch) = &self.else_branch {
+            else_branch.resolve(resolver);
+        }
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        self.condition.compile(compiler);
+
+        let then_jump = compiler.emit_jump(OpCode::JumpIfFalse);
+        compiler.emit_op(OpCode::Pop);
+        self.then_branch.compile(compiler);
+        let else_jump = compiler.emit_jump(OpCode::Jump);
+
+        compiler.patch_jump(then_jump);
+        compiler.emit_op(OpCode::Pop);
+        if let Some(else_branch) = &self.else_branch {
+            else_branch.compile(compiler);
+        }
+        compiler.patch_jump(else_jump);
+    }
+}
+
+impl<T> fmt::Display for If<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.else_branch {
+            Some(else_branch) => write!(
+                f,
+                "(if {} {} {})",
+                self.condition, self.then_branch, else_branch
+            ),
+            None => write!(f, "(if {} {})", self.condition, self.then_branch),
+        }
+    }
+}
+
+expr!(Expr: While<T> => condition: Box<T>, body: Box<dyn Stmt>);
+
+impl<T> Stmt for While<T>
+where
+    T: Expr + ?Sized,
+{
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        while self.condition.interpret(interpreter)?.is_truthy() {
+            self.body.execute(interpreter)?;
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        self.condition.resolve(resolver);
+        self.body.resolve(resolver);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        let loop_start = compiler.offset();
+        self.condition.compile(compiler);
+
+        let exit_jump = compiler.emit_jump(OpCode::JumpIfFalse);
+        compiler.emit_op(OpCode::Pop);
+        self.body.compile(compiler);
+        compiler.emit_loop(loop_start);
+
+        compiler.patch_jump(exit_jump);
+        compiler.emit_op(OpCode::Pop);
+    }
+}
+
+impl<T> fmt::Display for While<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(while {} {})", self.condition, self.body)
+    }
+}
+
+expr!(Expr: Function => declaration: Rc<FunctionDecl>);
+
+impl Stmt for Function {
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        let function = LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::clone(&interpreter.environment),
+            false,
+        );
+
+        interpreter.environment.borrow_mut().define(
+            &self.declaration.name.lexeme,
+            Value::Function(Rc::new(function)),
+        );
+        Ok(())
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        // The name is defined before the body is resolved
+        // so that the function can refer to itself
+        resolver.declare(&self.declaration.name);
+        resolver.define(&self.declaration.name);
+
+        resolver.resolve_function(&self.declaration, FunctionType::Function);
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        // Marked as initialized right away so that the function can refer to itself
+        compiler.declare_variable(&self.declaration.name);
+        compiler.mark_initialized();
+
+        compiler.function(&self.declaration, FunctionType::Function);
+        compiler.define_variable(&self.declaration.name);
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(fun {})", self.declaration)
+    }
+}
+
+expr!(Expr: Return<T> => keyword: Token, value: Option<Box<T>>);
+
+impl<T> Stmt for Return<T>
+where
+    T: Expr + ?Sized,
+{
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        let value = match &self.value {
+            Some(value) => value.interpret(interpreter)?,
+            None => Value::Nil,
+        };
+
+        Err(Unwind::Return(value))
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        if resolver.current_function == FunctionType::None {
+            resolver.error(&self.keyword, "Can't return from top-level code.");
+        }
+
+        if let Some(value) = &self.value {
+            if resolver.current_function == FunctionType::Initializer {
+                resolver.report(
+                    Diagnostic::new(
+                        self.keyword.span,
+                        "Can't return a value from an initializer.",
+                    )
+                    .with_note("initializers always return 'this'"),
+                );
+            }
+
+            value.resolve(resolver);
+        }
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        compiler.set_position(self.keyword.span.start);
+
+        match &self.value {
+            Some(value) => {
+                value.compile(compiler);
+                compiler.emit_op(OpCode::Return);
+            }
+            None => compiler.emit_return(),
+        }
+    }
+}
+
+impl<T> fmt::Display for Return<T>
+where
+    T: Expr + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "(return {})", value),
+            None => write!(f, "(return)"),
+        }
+    }
+}
+
+expr!(Expr: Class => name: Token, superclass: Option<Variable>, methods: Vec<Rc<FunctionDecl>>);
+
+impl Stmt for Class {
+    fn execute(&self, interpreter: &mut Interpreter) -> ExecResult {
+        let superclass = match &self.superclass {
+            Some(variable) => match variable.interpret(interpreter)? {
+                Value::Class(superclass) => Some(superclass),
+                _ => {
+                    let error = RuntimeError::new(&variable.name, "Superclass must be a class.");
+                    return Err(error.into());
+                }
+            },
+            None => None,
+        };
+
+        interpreter
+            .environment
+            .borrow_mut()
+            .define(&self.name.lexeme, Value::Nil);
+
+        // Methods of a subclass close over an extra scope in
+        // which 'super' refers to the superclass
+        let enclosing = Rc::clone(&interpreter.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(Rc::clone(&enclosing));
+            environment.define("super", Value::Class(Rc::clone(superclass)));
+            interpreter.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods = HashMap::new();
+        for method in &self.methods {
+            let function = LoxFunction::new(
+                Rc::clone(method),
+                Rc::clone(&interpreter.environment),
+                method.name.lexeme == "init",
+            );
+            methods.insert(
+                method.name.lexeme.clone(),
+                Value::Function(Rc::new(function)),
+            );
+        }
+
+        interpreter.environment = enclosing;
+
+        let class = LoxClass::new(self.name.lexeme.clone(), superclass, methods);
+        interpreter
+            .environment
+            .borrow_mut()
+            .assign(&self.name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    fn resolve(&self, resolver: &mut Resolver) {
+        let enclosing_class = resolver.current_class;
+        resolver.current_class = ClassType::Class;
+
+        resolver.declare(&self.name);
+        resolver.define(&self.name);
+
+        if let Some(superclass) = &self.superclass {
+            if superclass.name.lexeme == self.name.lexeme {
+                resolver.error(&superclass.name, "A class can't inherit from itself.");
+            }
+
+            resolver.current_class = ClassType::Subclass;
+            superclass.resolve(resolver);
+
+            resolver.begin_scope();
+            resolver.define_implicit("super");
+        }
+
+        resolver.begin_scope();
+        resolver.define_implicit("this");
+
+        for method in &self.methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            resolver.resolve_function(method, function_type);
+        }
+
+        resolver.end_scope();
+
+        if self.superclass.is_some() {
+            resolver.end_scope();
+        }
+
+        resolver.current_class = enclosing_class;
+    }
+
+    fn compile(&self, compiler: &mut Compiler) {
+        // A local class starts out as nil so that its methods can
+        // capture it. A global one is only defined once the class has
+        // been created, so that nothing is defined when the superclass
+        // isn't a class, just as in the tree-walker.
+        let name = compiler.identifier_constant(&self.name.lexeme);
+        let is_global = compiler.is_global_scope();
+        if !is_global {
+            compiler.declare_variable(&self.name);
+            compiler.set_position(self.name.span.start);
+            compiler.emit_op(OpCode::Nil);
+            compiler.define_variable(&self.name);
+        }
+
+        // The superclass is kept on the stack, in a scope of its own,
+        // as the local variable 'super' captured by the methods
+        if let Some(superclass) = &self.superclass {
+            compiler.begin_scope();
+            superclass.compile(compiler);
+            compiler.add_local("super");
+            compiler.mark_initialized();
+        }
+
+        for method in &self.methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            compiler.function(method, function_type);
+        }
+
+        match &self.superclass {
+            Some(superclass) => compiler.emit_op_for(OpCode::Class, &superclass.name),
+            None => compiler.emit_op(OpCode::Class),
+        }
+        compiler.emit_u16(name);
+        compiler.emit_u16(self.methods.len() as u16);
+        compiler.emit_byte(self.superclass.is_some() as u8);
+
+        // The scope of 'super' is still open, so the global is
+        // defined directly
+        if is_global {
+            compiler.define_global(&self.name);
+        } else {
+            compiler.set_variable(&self.name);
+            compiler.emit_op(OpCode::Pop);
+        }
+
+        if self.superclass.is_some() {
+            compiler.end_scope();
+        }
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(class {}", self.name)?;
+        if let Some(superclass) = &self.superclass {
+            write!(f, " < {}", superclass)?;
+        }
+        for method in &self.methods {
+            write!(f, " ({})", method)?;
+        }
+        write!(f, ")")
+    }
+}
@@ -0,0 +1,159 @@
+use crate::chunk::{Chunk, FunctionProto, OpCode};
+use std::io::{self, Write};
+
+// Writes every instruction of the function, followed by the
+// functions declared inside it
+pub fn disassemble_function(function: &FunctionProto, out: &mut dyn Write) -> io::Result<()> {
+    disassemble_chunk(&function.chunk, &function.to_string(), out)?;
+
+    for nested in &function.chunk.functions {
+        disassemble_function(nested, out)?;
+    }
+    Ok(())
+}
+
+pub fn disassemble_chunk(chunk: &Chunk, name: &str, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "== {} ==", name)?;
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, out)?;
+    }
+    Ok(())
+}
+
+// Writes the instruction at the offset and returns the offset
+// of the next one
+pub fn disassemble_instruction(
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    write!(out, "{:04} ", offset)?;
+    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+        write!(out, "   | ")?;
+    } else {
+        write!(out, "{:4} ", chunk.lines[offset])?;
+    }
+
+    let op = match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => op,
+        None => {
+            writeln!(out, "Unknown opcode {}", chunk.code[offset])?;
+            return Ok(offset + 1);
+        }
+    };
+    let name = format!("{:?}", op);
+
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper => constant_instruction(&name, chunk, offset, out),
+        OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue | OpCode::SetUpvalue => {
+            u16_instruction(&name, chunk, offset, out)
+        }
+        OpCode::Call => {
+            writeln!(out, "{:<16} {:4}", name, chunk.code[offset + 1])?;
+            Ok(offset + 2)
+        }
+        OpCode::Jump | OpCode::JumpIfFalse => jump_instruction(&name, true, chunk, offset, out),
+        OpCode::Loop => jump_instruction(&name, false, chunk, offset, out),
+        OpCode::Closure => closure_instruction(&name, chunk, offset, out),
+        OpCode::Class => {
+            let index = chunk.read_u16(offset + 1);
+            let method_count = chunk.read_u16(offset + 3);
+            let superclass = if chunk.code[offset + 5] == 1 {
+                " with superclass"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "{:<16} {:4} '{}' {} methods{}",
+                name, index, chunk.constants[index as usize], method_count, superclass
+            )?;
+            Ok(offset + 6)
+        }
+        _ => {
+            writeln!(out, "{}", name)?;
+            Ok(offset + 1)
+        }
+    }
+}
+
+fn constant_instruction(
+    name: &str,
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let index = chunk.read_u16(offset + 1);
+    writeln!(
+        out,
+        "{:<16} {:4} '{}'",
+        name, index, chunk.constants[index as usize]
+    )?;
+    Ok(offset + 3)
+}
+
+fn u16_instruction(
+    name: &str,
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    writeln!(out, "{:<16} {:4}", name, chunk.read_u16(offset + 1))?;
+    Ok(offset + 3)
+}
+
+fn jump_instruction(
+    name: &str,
+    forward: bool,
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let jump = chunk.read_u16(offset + 1) as usize;
+    let target = if forward {
+        offset + 3 + jump
+    } else {
+        offset + 3 - jump
+    };
+    writeln!(out, "{:<16} {:4} -> {}", name, offset, target)?;
+    Ok(offset + 3)
+}
+
+// Closures are followed by a pair of operands for every upvalue
+// the function captures
+fn closure_instruction(
+    name: &str,
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let index = chunk.read_u16(offset + 1);
+    let function = &chunk.functions[index as usize];
+    writeln!(out, "{:<16} {:4} {}", name, index, function)?;
+
+    let mut offset = offset + 3;
+    for _ in 0..function.upvalue_count {
+        let kind = if chunk.code[offset] == 1 {
+            "local"
+        } else {
+            "upvalue"
+        };
+        let index = chunk.read_u16(offset + 1);
+        writeln!(
+            out,
+            "{:04}    |                     {} {}",
+            offset, kind, index
+        )?;
+        offset += 3;
+    }
+
+    Ok(offset)
+}
@@ -0,0 +1,92 @@
+use crate::{
+    core::{RuntimeError, RuntimeResult, Token},
+    value::Value,
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+// Maps variable names to their values within a single scope.
+// Each scope links to the one enclosing it, so lookups walk
+// outwards until they reach the global scope.
+#[derive(Default)]
+pub(crate) struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // Redefining an existing variable simply overwrites it
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    // Reads a variable defined in this scope, ignoring enclosing ones
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn get(&self, name: &Token) -> RuntimeResult {
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    // Reads a variable from the environment the given number of
+    // scopes away, which must be known to contain it
+    pub fn get_at(&self, distance: usize, name: &str) -> Value {
+        if distance == 0 {
+            return self.values.get(name).cloned().unwrap_or(Value::Nil);
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => Value::Nil,
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), value);
+            return;
+        }
+
+        if let Some(enclosing) = &self.enclosing {
+            enclosing.borrow_mut().assign_at(distance - 1, name, value);
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined_variable(name)),
+        }
+    }
+}
+
+fn undefined_variable(name: &Token) -> RuntimeError {
+    let message = format!("Undefined variable '{}'.", name.lexeme);
+    RuntimeError::new(name, &message)
+}
@@ -1,18 +1,285 @@
-use crate::core::{Token, TokenType};
+use crate::core::{Position, Span, TraceFrame};
+use std::{
+    cell::RefCell,
+    error, fmt,
+    io::{self, IsTerminal},
+    rc::Rc,
+};
 
-fn report(line: usize, location: &str, message: &str) {
-    eprintln!("[line {}] Error{}: {}", line, location, message);
+// The source code an error was found in, so that diagnostics
+// can quote the lines they point at
+pub struct Source<'a> {
+    pub name: &'a str,
+    pub text: &'a str,
 }
 
-pub fn error(line: usize, message: &str) {
-    report(line, "", message);
+// Receives every error found while running a program
+pub trait ErrorReporter {
+    fn report(&mut self, diagnostic: &Diagnostic, source: &Source);
 }
 
-pub fn token_error(token: &Token, message: &str) {
-    if token.token_type == TokenType::EOF {
-        report(token.line, " at end", message);
-    } else {
-        let location = " at '".to_string() + &token.lexeme + "'";
-        report(token.line, &location, message);
+// Prints errors to stderr, in color when it is a terminal
+pub struct StderrReporter;
+
+impl ErrorReporter for StderrReporter {
+    fn report(&mut self, diagnostic: &Diagnostic, source: &Source) {
+        let color = io::stderr().is_terminal();
+        eprint!("{}", diagnostic.render(source, color));
+    }
+}
+
+// Keeps the errors rendered without color. Clones share the same
+// errors, so one can be given to the interpreter and the other
+// used to read what was reported.
+#[derive(Clone, Default)]
+pub struct CollectingReporter {
+    reports: Rc<RefCell<Vec<String>>>,
+}
+
+impl CollectingReporter {
+    pub fn new() -> CollectingReporter {
+        CollectingReporter::default()
+    }
+
+    pub fn reports(&self) -> Vec<String> {
+        self.reports.borrow().clone()
+    }
+}
+
+impl ErrorReporter for CollectingReporter {
+    fn report(&mut self, diagnostic: &Diagnostic, source: &Source) {
+        self.reports
+            .borrow_mut()
+            .push(diagnostic.render(source, false));
+    }
+}
+
+// How many calls are shown at each end of a long trace
+const TRACE_ENDS: usize = 10;
+
+// An error pointing at a span of the source, with optional
+// notes and help text printed below the quoted source line
+#[derive(Debug)]
+pub struct Diagnostic {
+    message: String,
+    // Missing for errors that don't come from the source, such as
+    // a host calling a global that doesn't exist
+    span: Option<Span>,
+    label: Option<String>,
+    notes: Vec<String>,
+    help: Option<String>,
+    // The calls being run when a runtime error happened
+    trace: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(span: Span, message: &str) -> Diagnostic {
+        Diagnostic {
+            message: message.to_string(),
+            span: Some(span),
+            label: None,
+            notes: Vec::new(),
+            help: None,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn at(position: Position, message: &str) -> Diagnostic {
+        let span = Span {
+            offset: 0,
+            len: 0,
+            start: position,
+            end: position,
+        };
+        Diagnostic::new(span, message)
+    }
+
+    pub fn without_span(message: &str) -> Diagnostic {
+        Diagnostic {
+            span: None,
+            ..Diagnostic::new(Span::default(), message)
+        }
+    }
+
+    // Text printed next to the caret
+    pub fn with_label(mut self, label: &str) -> Diagnostic {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Diagnostic {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    // Lists the calls below the error. A single call is left out,
+    // as then the error didn't happen inside a function. Only the
+    // ends of a long trace are kept, such as after a stack overflow.
+    pub fn with_trace(mut self, trace: &[TraceFrame]) -> Diagnostic {
+        if trace.len() <= 1 {
+            return self;
+        }
+
+        if trace.len() > 2 * TRACE_ENDS {
+            let hidden = trace.len() - 2 * TRACE_ENDS;
+            let innermost = trace[..TRACE_ENDS].iter().map(|frame| frame.to_string());
+            let outermost = trace[trace.len() - TRACE_ENDS..]
+                .iter()
+                .map(|frame| frame.to_string());
+            self.trace = innermost
+                .chain(Some(format!("... {} more calls ...", hidden)))
+                .chain(outermost)
+                .collect();
+        } else {
+            self.trace = trace.iter().map(|frame| frame.to_string()).collect();
+        }
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        self.span.map(|span| span.start)
+    }
+
+    // Formats the error as it is shown to the user, quoting the
+    // line of the source that it points at
+    pub fn render(&self, source: &Source, color: bool) -> String {
+        let style = Style::new(color);
+        let mut out = self.render_error(source, &style);
+        for frame in &self.trace {
+            out += &format!("{}\n", frame);
+        }
+        out
+    }
+
+    fn render_error(&self, source: &Source, style: &Style) -> String {
+        let mut out = format!(
+            "{}: {}\n",
+            style.paint(style.error, "error"),
+            style.paint(style.bold, &self.message)
+        );
+
+        // There is nothing to point at, only the message and notes
+        let span = match self.span {
+            Some(span) => span,
+            None => return out + &self.render_footer("", style),
+        };
+        let start = span.start;
+
+        let location = format!("{}:{}", source.name, start);
+        // Split on newlines rather than using lines(), so that the
+        // end of a file ending in a newline still has a line to show
+        let line = source
+            .text
+            .split('\n')
+            .nth(start.line.wrapping_sub(1))
+            .map(|line| line.trim_end_matches('\r'));
+        let line = match line {
+            Some(line) => line,
+            None => {
+                out += &format!("  {} {}\n", style.paint(style.gutter, "-->"), location);
+                return out + &self.render_footer("  ", style);
+            }
+        };
+
+        let number = start.line.to_string();
+        let pad = " ".repeat(number.len());
+        let bar = style.paint(style.gutter, "|");
+        out += &format!("{}{} {}\n", pad, style.paint(style.gutter, "-->"), location);
+        out += &format!("{} {}\n", pad, bar);
+        out += &format!("{} {} {}\n", style.paint(style.gutter, &number), bar, line);
+
+        // Keep tabs in the padding so the carets line up with the
+        // quoted line however wide the terminal draws them
+        let before: String = line
+            .chars()
+            .take(start.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let line_width = line.chars().count() + 1;
+        let width = if span.end.line == start.line {
+            span.end.column.saturating_sub(start.column)
+        } else {
+            line_width.saturating_sub(start.column)
+        };
+        let mut carets = "^".repeat(width.max(1));
+        if let Some(label) = &self.label {
+            carets = carets + " " + label;
+        }
+        out += &format!(
+            "{} {} {}{}\n",
+            pad,
+            bar,
+            before,
+            style.paint(style.error, &carets)
+        );
+
+        if !self.notes.is_empty() || self.help.is_some() {
+            out += &format!("{} {}\n", pad, bar);
+        }
+        out + &self.render_footer(&pad, style)
+    }
+
+    fn render_footer(&self, pad: &str, style: &Style) -> String {
+        let mut out = String::new();
+        for note in &self.notes {
+            out += &format!("{} = {}: {}\n", pad, style.paint(style.bold, "note"), note);
+        }
+        if let Some(help) = &self.help {
+            out += &format!("{} = {}: {}\n", pad, style.paint(style.bold, "help"), help);
+        }
+        out
+    }
+}
+
+// Without the source, an error can only be shown with its position
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position() {
+            Some(position) => write!(f, "{}: {}", position, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl error::Error for Diagnostic {}
+
+// ANSI escape codes, left empty when color is turned off
+struct Style {
+    error: &'static str,
+    gutter: &'static str,
+    bold: &'static str,
+    reset: &'static str,
+}
+
+impl Style {
+    fn new(color: bool) -> Style {
+        if color {
+            Style {
+                error: "\x1b[1;31m",
+                gutter: "\x1b[1;34m",
+                bold: "\x1b[1m",
+                reset: "\x1b[0m",
+            }
+        } else {
+            Style {
+                error: "",
+                gutter: "",
+                bold: "",
+                reset: "",
+            }
+        }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        format!("{}{}{}", code, text, self.reset)
     }
 }
@@ -0,0 +1,95 @@
+use crate::{
+    class::LoxInstance,
+    core::{FunctionDecl, RuntimeResult, Unwind},
+    environment::Environment,
+    interpreter::Interpreter,
+    value::Value,
+};
+use std::{cell::RefCell, fmt, rc::Rc};
+
+// Implemented by every value that can be invoked with a call expression
+pub trait Callable {
+    fn arity(&self) -> usize;
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> RuntimeResult;
+}
+
+// A function declared in Lox code, together with the
+// environment that was active when it was declared
+pub struct LoxFunction {
+    pub(crate) declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub(crate) fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> LoxFunction {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    // Creates a copy of the method whose closure has 'this' bound to the instance
+    pub(crate) fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", Value::Instance(instance));
+
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+}
+
+impl Callable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> RuntimeResult {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+
+        let result = match interpreter.execute_block(&self.declaration.body, environment) {
+            Err(Unwind::Error(mut error)) => {
+                error.add_frame(&self.declaration.name.lexeme);
+                Err(Unwind::Error(error))
+            }
+            result => result,
+        };
+
+        // An initializer always returns the instance it was called on
+        if self.is_initializer {
+            if let Err(Unwind::Error(error)) = result {
+                return Err(error);
+            }
+            return Ok(self.closure.borrow().get_at(0, "this"));
+        }
+
+        match result {
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name)
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{
+    core::{ExecResult, RuntimeError, RuntimeResult, Stmt, Token, Unwind, MAX_CALL_DEPTH},
+    environment::Environment,
+    stdlib,
+    value::Value,
+};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+// Holds the state that has to outlive a single run of the
+// interpreter, so that consecutive lines typed into the REPL
+// are evaluated within the same session.
+pub struct Interpreter {
+    pub(crate) globals: Rc<RefCell<Environment>>,
+    pub(crate) environment: Rc<RefCell<Environment>>,
+    // Where print statements write to
+    pub output: Box<dyn Write>,
+    // Calls in progress, counting a running script as one
+    call_depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let mut globals = Environment::new();
+        for (name, value) in stdlib::globals() {
+            globals.define(name, value);
+        }
+        let globals = Rc::new(RefCell::new(globals));
+
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            output: Box::new(io::stdout()),
+            call_depth: 0,
+        }
+    }
+
+    // Executes the statements in order, stopping at the first runtime error
+    pub fn interpret(&mut self, statements: &[Box<dyn Stmt>]) -> Result<(), RuntimeError> {
+        self.call_depth += 1;
+        let result = self.execute_all(statements);
+        self.call_depth -= 1;
+
+        match result {
+            Ok(()) | Err(Unwind::Return(_)) => Ok(()),
+            Err(Unwind::Error(mut error)) => {
+                error.add_frame("");
+                Err(error)
+            }
+        }
+    }
+
+    // Calls the value with the arguments. Errors from making the call
+    // itself, such as passing the wrong number of arguments, point at
+    // the token, which is the closing parenthesis of a call expression.
+    // Calls made by the host have no token, so neither do their errors.
+    pub fn call(
+        &mut self,
+        callee: &Value,
+        arguments: Vec<Value>,
+        paren: Option<&Token>,
+    ) -> RuntimeResult {
+        let error = |message: &str| match paren {
+            Some(paren) => RuntimeError::new(paren, message),
+            None => RuntimeError::without_span(message),
+        };
+
+        // Natives fail with a message, which is reported at the call
+        if let Value::Native(native) = callee {
+            return native.call(arguments).map_err(|message| error(&message));
+        }
+
+        let function = match callee.as_callable() {
+            Some(function) => function,
+            None => return Err(error("Can only call functions and classes.")),
+        };
+
+        if arguments.len() != function.arity() {
+            let message = format!(
+                "Expected {} arguments but got {}.",
+                function.arity(),
+                arguments.len()
+            );
+            return Err(error(&message));
+        }
+
+        // Each call recurses on the Rust stack, so it has to be limited
+        // before that runs out. A class without an initializer runs no
+        // code, just like the virtual machine doesn't give it a frame.
+        let runs_code = match callee {
+            Value::Class(class) => class.find_function("init").is_some(),
+            _ => true,
+        };
+        if !runs_code {
+            return function.call(self, arguments);
+        }
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(error("Stack overflow."));
+        }
+
+        self.call_depth += 1;
+        let result = function.call(self, arguments);
+        self.call_depth -= 1;
+        result
+    }
+
+    fn execute_all(&mut self, statements: &[Box<dyn Stmt>]) -> ExecResult {
+        for statement in statements {
+            statement.execute(self)?;
+        }
+
+        Ok(())
+    }
+
+    // Executes the statements inside the given environment. The previous
+    // environment is restored afterwards, even if execution was unwound
+    // by a return statement or a runtime error.
+    pub(crate) fn execute_block(
+        &mut self,
+        statements: &[Box<dyn Stmt>],
+        environment: Environment,
+    ) -> ExecResult {
+        let previous = Rc::clone(&self.environment);
+        self.environment = Rc::new(RefCell::new(environment));
+
+        let result = self.execute_all(statements);
+
+        self.environment = previous;
+        result
+    }
+
+    // Variables without a depth were not found by the resolver
+    // in any enclosing scope, so they must be globals
+    pub(crate) fn lookup_variable(&self, name: &Token, depth: Option<usize>) -> RuntimeResult {
+        match depth {
+            Some(distance) => Ok(self.environment.borrow().get_at(distance, &name.lexeme)),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    pub(crate) fn assign_variable(
+        &mut self,
+        name: &Token,
+        depth: Option<usize>,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        match depth {
+            Some(distance) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(distance, name, value);
+                Ok(())
+            }
+            None => self.globals.borrow_mut().assign(name, value),
+        }
+    }
+}
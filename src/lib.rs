@@ -0,0 +1,294 @@
+// A Lox interpreter with two backends: a tree-walker that runs the
+// syntax tree directly, and a compiler to bytecode for a virtual
+// machine. `Lox` runs source code from start to finish; the scanner,
+// parser, resolver and both backends can also be used on their own.
+
+mod chunk;
+mod class;
+mod compiler;
+mod core;
+mod debug;
+mod environment;
+mod error;
+mod function;
+mod interpreter;
+mod native;
+mod output;
+mod parser;
+mod resolver;
+mod scanner;
+mod stdlib;
+mod value;
+mod vm;
+
+// The syntax tree built by the parser
+pub mod ast {
+    pub use crate::core::{
+        Assign, Binary, Block, Call, Class, ExecResult, Expr, Expression, Function, FunctionDecl,
+        Get, Grouping, If, Literal, Logical, Print, Return, RuntimeResult, Set, Stmt, Super, This,
+        Unary, Unwind, Var, Variable, While,
+    };
+}
+
+pub use crate::chunk::FunctionProto;
+pub use crate::class::{LoxClass, LoxInstance};
+pub use crate::compiler::Compiler;
+pub use crate::core::{
+    LexError, LexResult, ParseError, Position, RuntimeError, Span, Token, TokenType, TraceFrame,
+    STACK_SIZE,
+};
+pub use crate::error::{CollectingReporter, Diagnostic, ErrorReporter, Source, StderrReporter};
+pub use crate::function::{Callable, LoxFunction};
+pub use crate::interpreter::Interpreter;
+pub use crate::native::{IntoNative, IntoNativeResult, NativeFunction, NativeResult};
+pub use crate::output::CollectingOutput;
+pub use crate::parser::Parser;
+pub use crate::resolver::Resolver;
+pub use crate::scanner::Scanner;
+pub use crate::value::{TypeError, Value};
+pub use crate::vm::{BoundMethod, Closure, Vm};
+
+use std::{cell::RefCell, fmt, io::Write, rc::Rc};
+
+// Why running some source code failed. The errors have already been
+// given to the error reporter by the time this is returned.
+#[derive(Debug, PartialEq)]
+pub enum LoxError {
+    Syntax,
+    Parse,
+    Resolve,
+    Compile,
+    Runtime,
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stage = match self {
+            LoxError::Syntax => "a syntax error",
+            LoxError::Parse => "a parse error",
+            LoxError::Resolve => "a resolution error",
+            LoxError::Compile => "a compile error",
+            LoxError::Runtime => "a runtime error",
+        };
+        write!(f, "The program failed with {}.", stage)
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+// The engine that executes a program once it has been parsed
+// and resolved. Both keep their state between runs.
+enum Backend {
+    TreeWalk(Interpreter),
+    Vm(Vm),
+}
+
+// How programs are run, as chosen on the command line
+#[derive(Default)]
+pub struct Options {
+    pub vm: bool,
+    // Print the bytecode of every compiled chunk before running it
+    pub disassemble: bool,
+    // Print the stack and each instruction as the VM executes it
+    pub trace: bool,
+}
+
+// Runs Lox source code through every stage, from scanning to
+// execution. Globals defined by one run are visible to the next.
+// Programs print to stdout and errors go to stderr unless another
+// output or error reporter is set. Deeply recursive programs need
+// a thread with a stack of STACK_SIZE bytes.
+pub struct Lox {
+    backend: Backend,
+    reporter: Box<dyn ErrorReporter>,
+    disassemble: bool,
+}
+
+impl Default for Lox {
+    fn default() -> Lox {
+        Lox::new(Options::default())
+    }
+}
+
+impl Lox {
+    // Bytecode can only be inspected when there is bytecode, so
+    // disassembling or tracing also selects the virtual machine
+    pub fn new(options: Options) -> Lox {
+        let backend = if options.vm || options.disassemble || options.trace {
+            let mut vm = Vm::new();
+            vm.trace = options.trace;
+            Backend::Vm(vm)
+        } else {
+            Backend::TreeWalk(Interpreter::new())
+        };
+
+        Lox {
+            backend,
+            reporter: Box::new(StderrReporter),
+            disassemble: options.disassemble,
+        }
+    }
+
+    // Sends what print statements write to the output
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        let output = Box::new(output);
+        match &mut self.backend {
+            Backend::TreeWalk(interpreter) => interpreter.output = output,
+            Backend::Vm(vm) => vm.output = output,
+        }
+    }
+
+    // Hands every error found in a program to the reporter
+    pub fn set_reporter<R: ErrorReporter + 'static>(&mut self, reporter: R) {
+        self.reporter = Box::new(reporter);
+    }
+
+    // Makes a Rust function or closure callable from Lox as a global
+    // with the given name. Its arguments are checked and converted
+    // from Lox values, and an error it returns becomes a runtime
+    // error at the call.
+    pub fn define_native<F, Args>(&mut self, name: &str, function: F)
+    where
+        F: IntoNative<Args>,
+    {
+        let native = NativeFunction::from_fn(name, function);
+        self.set_global(name, Value::Native(Rc::new(native)));
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        match &self.backend {
+            Backend::TreeWalk(interpreter) => interpreter.globals.borrow().lookup(name),
+            Backend::Vm(vm) => vm.get_global(name),
+        }
+    }
+
+    // Defines the global, or overwrites it if it already exists
+    pub fn set_global<V: Into<Value>>(&mut self, name: &str, value: V) {
+        let value = value.into();
+        match &mut self.backend {
+            Backend::TreeWalk(interpreter) => interpreter.globals.borrow_mut().define(name, value),
+            Backend::Vm(vm) => vm.define_global(name, value),
+        }
+    }
+
+    // Calls a function, class or native, usually one a script has
+    // defined. A runtime error is returned rather than reported;
+    // its diagnostic() can be given to a reporter if needed.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        match &mut self.backend {
+            Backend::TreeWalk(interpreter) => interpreter.call(callee, arguments, None),
+            Backend::Vm(vm) => vm.call_function(callee.clone(), arguments),
+        }
+    }
+
+    // Calls the global with the given name
+    pub fn call_function(
+        &mut self,
+        name: &str,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let callee = self.get_global(name).ok_or_else(|| {
+            let message = format!("Undefined variable '{}'.", name);
+            RuntimeError::without_span(&message)
+        })?;
+        self.call(&callee, arguments)
+    }
+
+    // Runs a whole program. The name is used when pointing at the
+    // source in error messages.
+    pub fn run(&mut self, name: &str, source: &str) -> Result<(), LoxError> {
+        self.run_source(name, source, false)
+    }
+
+    // Runs a line typed into the REPL, where the value of a lone
+    // expression is printed
+    pub fn run_repl(&mut self, name: &str, source: &str) -> Result<(), LoxError> {
+        self.run_source(name, source, true)
+    }
+
+    fn run_source(&mut self, name: &str, source: &str, repl: bool) -> Result<(), LoxError> {
+        let source = Source { name, text: source };
+        // Tokens are scanned as the parser asks for them. Lexical errors
+        // are kept along the way and the bad input skipped, so that
+        // syntax errors in the rest of the code are found too.
+        let lex_errors = RefCell::new(Vec::new());
+        let tokens = Scanner::new(source.text).filter_map(|result| match result {
+            Ok(token) => Some(token),
+            Err(error) => {
+                lex_errors.borrow_mut().push(error.diagnostic());
+                None
+            }
+        });
+
+        let mut parser = Parser::new(tokens);
+        let parsed = if repl {
+            parser.parse_repl()
+        } else {
+            parser.parse()
+        };
+        drop(parser);
+
+        let mut lex_errors = lex_errors.into_inner();
+        if !lex_errors.is_empty() {
+            // Both kinds of error are reported in the order they
+            // appear in the source
+            if let Err(parse_errors) = parsed {
+                lex_errors.extend(parse_errors);
+            }
+            lex_errors.sort_by_key(|diagnostic| {
+                let position = diagnostic.position();
+                position.map(|position| (position.line, position.column))
+            });
+            self.report(&lex_errors, &source);
+            return Err(LoxError::Syntax);
+        }
+        let statements = parsed.map_err(|diagnostics| {
+            self.report(&diagnostics, &source);
+            LoxError::Parse
+        })?;
+
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        if !resolver.diagnostics.is_empty() {
+            self.report(&resolver.diagnostics, &source);
+            return Err(LoxError::Resolve);
+        }
+
+        let result = match &mut self.backend {
+            Backend::TreeWalk(interpreter) => interpreter.interpret(&statements),
+            Backend::Vm(vm) => match Compiler::new().compile(&statements) {
+                Ok(function) => {
+                    let disassembled = if self.disassemble {
+                        debug::disassemble_function(&function, &mut vm.output)
+                    } else {
+                        Ok(())
+                    };
+                    match disassembled {
+                        Ok(()) => vm.interpret(function),
+                        Err(error) => {
+                            let message = format!("Couldn't write the output: {}.", error);
+                            Err(RuntimeError::without_span(&message))
+                        }
+                    }
+                }
+                Err(diagnostics) => {
+                    for diagnostic in &diagnostics {
+                        self.reporter.report(diagnostic, &source);
+                    }
+                    return Err(LoxError::Compile);
+                }
+            },
+        };
+
+        result.map_err(|error| {
+            self.reporter.report(&error.diagnostic(), &source);
+            LoxError::Runtime
+        })
+    }
+
+    fn report(&mut self, diagnostics: &[Diagnostic], source: &Source) {
+        for diagnostic in diagnostics {
+            self.reporter.report(diagnostic, source);
+        }
+    }
+}
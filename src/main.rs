@@ -1,50 +1,116 @@
-mod core;
-pub mod error;
-pub mod parser;
-pub mod scanner;
-
-use crate::parser::Parser;
-use crate::scanner::Scanner;
+use lox::{Lox, LoxError, Options, STACK_SIZE};
 use std::env;
 use std::fs;
-use std::io::ErrorKind;
+use std::io::{self, ErrorKind, Write};
 use std::process;
+use std::thread;
+
+// The command was used incorrectly, such as with an unknown option
+const EX_USAGE: i32 = 64;
+// The input data was incorrect, such as a script with a syntax error
+const EX_DATAERR: i32 = 65;
+// The script didn't exist or couldn't be read
+const EX_NOINPUT: i32 = 66;
+// An internal software error, such as a runtime error in a script
+const EX_SOFTWARE: i32 = 70;
+// An error while reading or writing, other than opening the script
+const EX_IOERR: i32 = 74;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // The tree-walker makes every call on the Rust stack, so programs
+    // are run on a thread with room for them to recurse deeply
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_command)
+        .expect("Couldn't start the interpreter thread");
+
+    if interpreter.join().is_err() {
+        process::exit(EX_SOFTWARE);
+    }
+}
+
+fn run_command() {
+    let mut options = Options::default();
+    let mut scripts = Vec::new();
 
-    match args.len() {
-        1 => run_prompt(),
-        2 => match fs::read_to_string(&args[1]) {
-            Ok(source) => run_file(source),
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--vm" => options.vm = true,
+            "--disassemble" => options.disassemble = true,
+            "--trace" => options.trace = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option: {}", arg);
+                print_usage();
+            }
+            _ => scripts.push(arg),
+        }
+    }
+
+    let lox = Lox::new(options);
+
+    match scripts.as_slice() {
+        [] => run_prompt(lox),
+        [script] => match fs::read_to_string(script) {
+            Ok(source) => run_file(lox, script, source),
             Err(err) => match err.kind() {
-                ErrorKind::NotFound => println!("Couldn't find: {}", args[1]),
-                ErrorKind::PermissionDenied => println!("Permission denied."),
-                _ => println!("There was a problem processing the file: {}", err),
+                ErrorKind::NotFound => {
+                    eprintln!("Couldn't find: {}", script);
+                    process::exit(EX_NOINPUT);
+                }
+                ErrorKind::PermissionDenied => {
+                    eprintln!("Permission denied.");
+                    process::exit(EX_NOINPUT);
+                }
+                _ => {
+                    eprintln!("There was a problem processing the file: {}", err);
+                    process::exit(EX_IOERR);
+                }
             },
         },
-        _ => {
-            println!("Usage: lox [script]");
-        }
+        _ => print_usage(),
     };
 }
 
-fn run_prompt() {
-    println!("Implement REPL");
+fn print_usage() -> ! {
+    eprintln!("Usage: lox [--vm] [--disassemble] [--trace] [script]");
+    process::exit(EX_USAGE);
 }
 
-fn run_file(source: String) {
-    let mut scanner = Scanner::new(&source);
+fn run_prompt(mut lox: Lox) {
+    let stdin = io::stdin();
 
-    if let Err(_) = scanner.scan_tokens() {
-        process::exit(1);
-    }
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("Couldn't flush stdout");
 
-    let mut parser = Parser::new(scanner.get_tokens());
-    if let Ok(expr) = parser.parse() {
-        match expr.interpret() {
-            Ok(object) => println!("{:#?}", object.downcast::<f64>().unwrap()),
-            Err(error) => println!("{:#?}", error.0),
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            // EOF, usually from Ctrl-D
+            Ok(0) => {
+                println!();
+                break;
+            }
+            Ok(_) => {
+                // Errors have already been reported, so the session just
+                // moves on to the next line
+                let _ = lox.run_repl("<stdin>", &line);
+            }
+            Err(err) => {
+                eprintln!("There was a problem reading the input: {}", err);
+                process::exit(EX_IOERR);
+            }
         }
     }
 }
+
+// Exits with the status codes from sysexits.h when the script fails
+fn run_file(mut lox: Lox, name: &str, source: String) {
+    match lox.run(name, &source) {
+        Ok(()) => (),
+        Err(LoxError::Syntax)
+        | Err(LoxError::Parse)
+        | Err(LoxError::Resolve)
+        | Err(LoxError::Compile) => process::exit(EX_DATAERR),
+        Err(LoxError::Runtime) => process::exit(EX_SOFTWARE),
+    }
+}
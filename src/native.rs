@@ -0,0 +1,155 @@
+use crate::value::{TypeError, Value};
+use std::{convert::TryFrom, fmt};
+
+// The result of calling a native function. Errors are messages that
+// get reported as runtime errors at the call that caused them.
+pub type NativeResult = Result<Value, String>;
+
+type NativeFn = Box<dyn Fn(Vec<Value>) -> NativeResult>;
+
+// A function implemented in Rust that Lox code can call
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    function: NativeFn,
+}
+
+impl NativeFunction {
+    // Wraps a function taking its arguments as Lox values. It is only
+    // called with as many arguments as the arity.
+    pub fn new<F>(name: &str, arity: usize, function: F) -> NativeFunction
+    where
+        F: Fn(Vec<Value>) -> NativeResult + 'static,
+    {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    // Wraps a Rust function or closure. Its arguments are converted
+    // from Lox values with TryFrom and its result back with From.
+    pub fn from_fn<F, Args>(name: &str, function: F) -> NativeFunction
+    where
+        F: IntoNative<Args>,
+    {
+        NativeFunction {
+            name: name.to_string(),
+            arity: F::ARITY,
+            function: function.into_native(name),
+        }
+    }
+
+    pub fn call(&self, arguments: Vec<Value>) -> NativeResult {
+        if arguments.len() != self.arity {
+            return Err(format!(
+                "Expected {} arguments but got {}.",
+                self.arity,
+                arguments.len()
+            ));
+        }
+
+        (self.function)(arguments)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// Implemented for Rust closures whose arguments can be converted
+// from Lox values. Args is the tuple of argument types, which
+// keeps the implementations for each arity apart.
+pub trait IntoNative<Args> {
+    const ARITY: usize;
+
+    fn into_native(self, name: &str) -> NativeFn;
+}
+
+// Implemented for everything a native function can return
+pub trait IntoNativeResult {
+    fn into_native_result(self) -> NativeResult;
+}
+
+macro_rules! into_native_result {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoNativeResult for $ty {
+                fn into_native_result(self) -> NativeResult {
+                    Ok(Value::from(self))
+                }
+            }
+        )*
+    };
+}
+
+into_native_result!(Value, f64, bool, String, ());
+
+// A returned error becomes a runtime error in the script
+impl<T: IntoNativeResult> IntoNativeResult for Result<T, String> {
+    fn into_native_result(self) -> NativeResult {
+        self.and_then(T::into_native_result)
+    }
+}
+
+// Converts the argument at the index, describing which argument
+// of which function had the wrong type if it can't be
+fn argument<T>(name: &str, index: usize, value: Value) -> Result<T, String>
+where
+    T: TryFrom<Value>,
+    TypeError: From<T::Error>,
+{
+    T::try_from(value).map_err(|error| {
+        format!(
+            "Argument {} of {}() must be {}.",
+            index + 1,
+            name,
+            TypeError::from(error).expected
+        )
+    })
+}
+
+// Parameters:
+//
+// $arity: The number of arguments
+//
+// $($arg:ident $index:tt),*: The type parameter of each argument,
+// followed by its position in the argument list
+macro_rules! into_native {
+    ($arity:expr; $($arg:ident $index:tt),*) => {
+        impl<F, R, $($arg,)*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoNativeResult,
+            $($arg: TryFrom<Value>, TypeError: From<$arg::Error>,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(unused_mut, unused_variables)]
+            fn into_native(self, name: &str) -> NativeFn {
+                let name = name.to_string();
+                Box::new(move |arguments| {
+                    let mut arguments = arguments.into_iter();
+                    self($(argument::<$arg>(&name, $index, arguments.next().unwrap())?),*)
+                        .into_native_result()
+                })
+            }
+        }
+    };
+}
+
+into_native!(0;);
+into_native!(1; A 0);
+into_native!(2; A 0, B 1);
+into_native!(3; A 0, B 1, C 2);
+into_native!(4; A 0, B 1, C 2, D 3);
+into_native!(5; A 0, B 1, C 2, D 3, E 4);
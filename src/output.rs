@@ -0,0 +1,34 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+// Keeps everything a program prints in memory. Clones share the
+// same buffer, so one can be given to the interpreter and the
+// other used to read what was printed.
+#[derive(Clone, Default)]
+pub struct CollectingOutput {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CollectingOutput {
+    pub fn new() -> CollectingOutput {
+        CollectingOutput::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.borrow()).into_owned()
+    }
+}
+
+impl Write for CollectingOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
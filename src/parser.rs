@@ -1,41 +1,336 @@
 use crate::{
     core::{
-        Binary, Grouping, Literal, ParseError, ParseResult, Token, TokenType, TokenType::*, Unary,
+        Binary, Block, Call, Class, Expr, Expression, Function, FunctionDecl, Get, Grouping, If,
+        Literal, Logical, ParseError, ParseResult, Print, Return, Stmt, StmtResult, Super, This,
+        Token, TokenType, TokenType::*, Unary, Var, Variable, While,
     },
-    error,
+    error::Diagnostic,
 };
-use std::{iter::Peekable, slice::Iter};
+use std::{iter::Peekable, rc::Rc};
 
-pub struct Parser<'a> {
-    reader: Peekable<Iter<'a, Token>>,
+const MAX_ARGUMENTS: usize = 255;
+
+// Parses tokens into a syntax tree. The tokens can come from any
+// iterator, so they don't all have to be scanned up front.
+pub struct Parser<I: Iterator<Item = Token>> {
+    reader: Peekable<I>,
     current: usize,
+    repl: bool,
+    // How many blocks the parser is inside of
+    block_depth: usize,
+    // Every syntax error found so far
+    diagnostics: Vec<Diagnostic>,
 }
 
-impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a Vec<Token>) -> Parser<'a> {
-        let reader = tokens.iter().peekable();
-
-        Parser { reader, current: 0 }
+impl<I: Iterator<Item = Token>> Parser<I> {
+    pub fn new<T>(tokens: T) -> Parser<I>
+    where
+        T: IntoIterator<Item = Token, IntoIter = I>,
+    {
+        let reader = tokens.into_iter().peekable();
+
+        Parser {
+            reader,
+            current: 0,
+            repl: false,
+            block_depth: 0,
+            diagnostics: Vec::new(),
+        }
     }
 }
 
-impl<'a> Parser<'a> {
-    pub fn parse(&mut self) -> ParseResult {
-        self.expression()
+impl<I: Iterator<Item = Token>> Parser<I> {
+    // Parses the whole program. After a syntax error parsing carries
+    // on from the next statement, so that every error is returned.
+    pub fn parse(&mut self) -> Result<Vec<Box<dyn Stmt>>, Vec<Diagnostic>> {
+        let mut statements = Vec::new();
+
+        while !self.is_at_end() {
+            if let Some(statement) = self.recover(Self::declaration) {
+                statements.push(statement);
+            }
+        }
+
+        if !self.diagnostics.is_empty() {
+            return Err(std::mem::take(&mut self.diagnostics));
+        }
+        Ok(statements)
+    }
+
+    // Parses a line typed into the REPL. A trailing expression
+    // without a semicolon is turned into a print statement so that
+    // its value is shown to the user.
+    pub fn parse_repl(&mut self) -> Result<Vec<Box<dyn Stmt>>, Vec<Diagnostic>> {
+        self.repl = true;
+        self.parse()
+    }
+
+    fn declaration(&mut self) -> StmtResult {
+        if self.match_token(Class) {
+            return self.class_declaration();
+        }
+
+        if self.match_token(Fun) {
+            return Ok(Box::new(Function::new(self.function("function")?)));
+        }
+
+        if self.match_token(Var) {
+            return self.var_declaration();
+        }
+
+        self.statement()
+    }
+
+    fn class_declaration(&mut self) -> StmtResult {
+        let name = self.consume(Identifier, "Expect class name.")?;
+
+        let mut superclass = None;
+        if self.match_token(Less) {
+            let superclass_name = self.consume(Identifier, "Expect superclass name.")?;
+            superclass = Some(Variable::new(superclass_name));
+        }
+
+        self.consume(LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Box::new(Class::new(name, superclass, methods)))
+    }
+
+    // Parses the name, parameters and body of a function. The kind
+    // is used to tell functions and methods apart in error messages.
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionDecl>, ParseError> {
+        let name = self.consume(Identifier, &format!("Expect {} name.", kind))?;
+        self.consume(LeftParen, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = Vec::new();
+        if !self.check(RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let token = self.peek_token();
+                    self.report(&token, "Can't have more than 255 parameters.");
+                }
+
+                params.push(self.consume(Identifier, "Expect parameter name.")?);
+
+                if !self.match_token(Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+        let body = self.block()?;
+
+        Ok(Rc::new(FunctionDecl::new(name, params, body)))
+    }
+
+    fn var_declaration(&mut self) -> StmtResult {
+        let name = self.consume(Identifier, "Expect variable name.")?;
+
+        let mut initializer = None;
+        if self.match_token(Equal) {
+            initializer = Some(self.expression()?);
+        }
+
+        self.consume(SemiColon, "Expect ';' after variable declaration.")?;
+        Ok(Box::new(Var::new(name, initializer)))
+    }
+
+    fn statement(&mut self) -> StmtResult {
+        if self.match_token(For) {
+            return self.for_statement();
+        }
+
+        if self.match_token(If) {
+            return self.if_statement();
+        }
+
+        if self.match_token(Print) {
+            return self.print_statement();
+        }
+
+        if let Some(keyword) = self.check_next_token(|token| token.token_type == Return) {
+            return self.return_statement(keyword);
+        }
+
+        if self.match_token(While) {
+            return self.while_statement();
+        }
+
+        if self.match_token(LeftBrace) {
+            return Ok(Box::new(Block::new(self.block()?)));
+        }
+
+        self.expression_statement()
+    }
+
+    // Parses the statements of a block whose opening brace was consumed
+    fn block(&mut self) -> Result<Vec<Box<dyn Stmt>>, ParseError> {
+        let mut statements = Vec::new();
+
+        self.block_depth += 1;
+        while !self.check(RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.recover(Self::declaration) {
+                statements.push(statement);
+            }
+        }
+        self.block_depth -= 1;
+
+        self.consume(RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    // Desugars a for loop into an equivalent while loop
+    fn for_statement(&mut self) -> StmtResult {
+        self.consume(LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.match_token(SemiColon) {
+            None
+        } else if self.match_token(Var) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(SemiColon) {
+            Box::new(Literal::new(True))
+        } else {
+            self.expression()?
+        };
+        self.consume(SemiColon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Box::new(Block::new(vec![body, Box::new(Expression::new(increment))]));
+        }
+
+        body = Box::new(While::new(condition, body));
+
+        if let Some(initializer) = initializer {
+            body = Box::new(Block::new(vec![initializer, body]));
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> StmtResult {
+        self.consume(LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(RightParen, "Expect ')' after if condition.")?;
+
+        // An else is bound to the nearest if that precedes it
+        let then_branch = self.statement()?;
+        let mut else_branch = None;
+        if self.match_token(Else) {
+            else_branch = Some(self.statement()?);
+        }
+
+        Ok(Box::new(If::new(condition, then_branch, else_branch)))
+    }
+
+    fn return_statement(&mut self, keyword: Token) -> StmtResult {
+        let mut value = None;
+        if !self.check(SemiColon) {
+            value = Some(self.expression()?);
+        }
+
+        self.consume(SemiColon, "Expect ';' after return value.")?;
+        Ok(Box::new(Return::new(keyword, value)))
+    }
+
+    fn while_statement(&mut self) -> StmtResult {
+        self.consume(LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+
+        Ok(Box::new(While::new(condition, body)))
+    }
+
+    fn print_statement(&mut self) -> StmtResult {
+        let value = self.expression()?;
+        self.consume(SemiColon, "Expect ';' after value.")?;
+        Ok(Box::new(Print::new(value)))
+    }
+
+    fn expression_statement(&mut self) -> StmtResult {
+        let expr = self.expression()?;
+
+        if self.repl && self.is_at_end() {
+            return Ok(Box::new(Print::new(expr)));
+        }
+
+        self.consume(SemiColon, "Expect ';' after expression.")?;
+        Ok(Box::new(Expression::new(expr)))
     }
 
     fn expression(&mut self) -> ParseResult {
-        self.equality()
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> ParseResult {
+        let expr = self.or()?;
+
+        if let Some(equals) = self.check_next_token(|token| token.token_type == Equal) {
+            let value = self.assignment()?;
+
+            // The parser isn't confused by a bad target, so there is
+            // no need to synchronize
+            return match expr.assign(value) {
+                Some(assignment) => Ok(assignment),
+                None => {
+                    self.report(&equals, "Invalid assignment target.");
+                    Ok(Box::new(Literal::new(Nil)))
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> ParseResult {
+        let mut expr = self.and()?;
+
+        while let Some(token) = self.check_next_token(|token| token.token_type == Or) {
+            let right_expr = self.and()?;
+            expr = Box::new(Logical::new(expr, token, right_expr));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult {
+        let mut expr = self.equality()?;
+
+        while let Some(token) = self.check_next_token(|token| token.token_type == And) {
+            let right_expr = self.equality()?;
+            expr = Box::new(Logical::new(expr, token, right_expr));
+        }
+
+        Ok(expr)
     }
 
     fn equality(&mut self) -> ParseResult {
         let mut expr = self.comparison()?;
 
-        while let Some(token) = self.check_next_token(|token| match token.token_type {
-            BangEqual | EqualEqual => true,
-            _ => false,
-        }) {
-            let token = token.clone();
+        while let Some(token) =
+            self.check_next_token(|token| matches!(token.token_type, BangEqual | EqualEqual))
+        {
             let right_expr = self.comparison()?;
             expr = Box::new(Binary::new(expr, token, right_expr));
         }
@@ -48,11 +343,9 @@ impl<'a> Parser<'a> {
     fn comparison(&mut self) -> ParseResult {
         let mut expr = self.addition()?;
 
-        while let Some(token) = self.check_next_token(|token| match token.token_type {
-            Greater | GreaterEqual | Less | LessEqual => true,
-            _ => false,
+        while let Some(token) = self.check_next_token(|token| {
+            matches!(token.token_type, Greater | GreaterEqual | Less | LessEqual)
         }) {
-            let token = token.clone();
             let right_expr = self.addition()?;
             expr = Box::new(Binary::new(expr, token, right_expr));
         }
@@ -65,11 +358,9 @@ impl<'a> Parser<'a> {
     fn addition(&mut self) -> ParseResult {
         let mut expr = self.multiplication()?;
 
-        while let Some(token) = self.check_next_token(|token| match token.token_type {
-            Minus | Plus => true,
-            _ => false,
-        }) {
-            let token = token.clone();
+        while let Some(token) =
+            self.check_next_token(|token| matches!(token.token_type, Minus | Plus))
+        {
             let right_expr = self.multiplication()?;
             expr = Box::new(Binary::new(expr, token, right_expr));
         }
@@ -82,11 +373,9 @@ impl<'a> Parser<'a> {
     fn multiplication(&mut self) -> ParseResult {
         let mut expr = self.unary()?;
 
-        while let Some(token) = self.check_next_token(|token| match token.token_type {
-            Slash | Star => true,
-            _ => false,
-        }) {
-            let token = token.clone();
+        while let Some(token) =
+            self.check_next_token(|token| matches!(token.token_type, Slash | Star))
+        {
             let right_expr = self.unary()?;
             expr = Box::new(Binary::new(expr, token, right_expr));
         }
@@ -97,45 +386,95 @@ impl<'a> Parser<'a> {
     }
 
     fn unary(&mut self) -> ParseResult {
-        if let Some(token) = self.check_next_token(|token| match token.token_type {
-            Bang | Minus => true,
-            _ => false,
-        }) {
-            let token = token.clone();
+        if let Some(token) = self.check_next_token(|token| matches!(token.token_type, Bang | Minus))
+        {
             let right_expr = self.unary()?;
             return Ok(Box::new(Unary::new(token, right_expr)));
         }
 
-        Ok(self.primary()?)
+        self.call()
+    }
+
+    fn call(&mut self) -> ParseResult {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_token(LeftParen) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(Dot) {
+                let name = self.consume(Identifier, "Expect property name after '.'.")?;
+                expr = Box::new(Get::new(expr, name));
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // Parses the arguments of a call whose opening parenthesis was consumed
+    fn finish_call(&mut self, callee: Box<dyn Expr>) -> ParseResult {
+        let mut arguments = Vec::new();
+        if !self.check(RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let token = self.peek_token();
+                    self.report(&token, "Can't have more than 255 arguments.");
+                }
+
+                arguments.push(self.expression()?);
+
+                if !self.match_token(Comma) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(RightParen, "Expect ')' after arguments.")?;
+
+        Ok(Box::new(Call::new(callee, paren, arguments)))
     }
 
     fn primary(&mut self) -> ParseResult {
-        if let Some(token) = self.check_next_token(|token| match token.token_type {
-            True | False | Nil | Number(_) | Str(_) => true,
-            _ => false,
+        if let Some(token) = self.check_next_token(|token| {
+            matches!(token.token_type, True | False | Nil | Number(_) | Str(_))
         }) {
-            return Ok(Box::new(Literal::new(token.token_type.clone())));
+            return Ok(Box::new(Literal::new(token.token_type)));
+        }
+
+        if let Some(keyword) = self.check_next_token(|token| token.token_type == Super) {
+            self.consume(Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(Identifier, "Expect superclass method name.")?;
+            return Ok(Box::new(Super::new(keyword, method)));
+        }
+
+        if let Some(keyword) = self.check_next_token(|token| token.token_type == This) {
+            return Ok(Box::new(This::new(keyword)));
         }
 
-        if let Some(_) = self.check_next_token(|token| token.token_type == LeftParen) {
+        if let Some(token) = self.check_next_token(|token| token.token_type == Identifier) {
+            return Ok(Box::new(Variable::new(token)));
+        }
+
+        if self.match_token(LeftParen) {
             let expr = self.expression()?;
             self.consume(RightParen, "Expect ')' after expression.")?;
             return Ok(Box::new(Grouping::new(expr)));
         }
 
         if let Some(token) = self.reader.peek() {
-            return Err(Parser::error(token, "Expected expression."));
+            return Err(Self::error(token, "Expected expression."));
         }
-        return Err(Parser::error(&Token::empty(), "Expected expression."));
+        Err(Self::error(&Token::empty(), "Expected expression."))
     }
 
     // Checks the next token and if it satisfies a closure, consumes it
-    fn check_next_token<T>(&mut self, f: T) -> Option<&Token>
+    fn check_next_token<T>(&mut self, f: T) -> Option<Token>
     where
         T: Fn(&Token) -> bool,
     {
         if let Some(c) = self.reader.peek() {
-            if f(*c) {
+            if f(c) {
                 return self.advance();
             } else {
                 return None;
@@ -145,37 +484,115 @@ impl<'a> Parser<'a> {
     }
 
     fn check_unexpected_expr(&mut self) -> Result<(), ParseError> {
-        if let Some(token) = self.check_next_token(|token| match token.token_type {
-            True | False | Nil | Number(_) | Str(_) | LeftParen | Identifier => true,
-            _ => false,
+        if let Some(token) = self.check_next_token(|token| {
+            matches!(
+                token.token_type,
+                True | False | Nil | Number(_) | Str(_) | LeftParen | Identifier
+            )
         }) {
-            return Err(Parser::error(token, "Unexpected Expression"));
+            return Err(Self::error(&token, "Unexpected Expression"));
         }
         Ok(())
     }
 
-    fn advance(&mut self) -> Option<&Token> {
+    // Consumes the next token if it is of the given type
+    fn match_token(&mut self, token_type: TokenType) -> bool {
+        self.check_next_token(|token| token.token_type == token_type)
+            .is_some()
+    }
+
+    // Checks the type of the next token without consuming it
+    fn check(&mut self, token_type: TokenType) -> bool {
+        match self.reader.peek() {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
+
+    fn peek_token(&mut self) -> Token {
+        match self.reader.peek() {
+            Some(token) => (*token).clone(),
+            None => Token::empty(),
+        }
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        match self.reader.peek() {
+            Some(token) => token.token_type == EOF,
+            None => true,
+        }
+    }
+
+    fn advance(&mut self) -> Option<Token> {
         self.current += 1;
         self.reader.next()
     }
 
     fn error(token: &Token, message: &str) -> ParseError {
-        error::token_error(token, message);
+        ParseError {
+            diagnostic: Box::new(Diagnostic::new(token.span, message)),
+        }
+    }
 
-        ParseError {}
+    // Records an error that doesn't leave the parser confused
+    // about where it is, so parsing can simply go on
+    fn report(&mut self, token: &Token, message: &str) {
+        self.diagnostics.push(Diagnostic::new(token.span, message));
+    }
+
+    // Runs the parsing function, and if it fails, skips tokens until
+    // the start of the next statement so parsing can resume there
+    fn recover<T, F>(&mut self, parse: F) -> Option<T>
+    where
+        F: Fn(&mut Self) -> Result<T, ParseError>,
+    {
+        match parse(self) {
+            Ok(result) => Some(result),
+            Err(error) => {
+                self.diagnostics.push(*error.diagnostic);
+                self.synchronize();
+                None
+            }
+        }
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
-        if let Some(next_token) = self.reader.peek() {
-            let token = next_token.clone();
-            if token.token_type == token_type {
-                self.advance();
-                return Ok(token);
+    // Discards tokens until just after a semicolon, or just before
+    // a keyword that begins a statement. Inside a block it also stops
+    // before a closing brace, which is left for the block to consume.
+    fn synchronize(&mut self) {
+        loop {
+            if self.block_depth > 0 && self.check(RightBrace) {
+                return;
+            }
+
+            let token = match self.advance() {
+                Some(token) => token,
+                None => return,
+            };
+            if token.token_type == SemiColon {
+                return;
+            }
+
+            if let Some(next) = self.reader.peek() {
+                if matches!(
+                    next.token_type,
+                    Class | Fun | Var | For | If | While | Print | Return | EOF
+                ) {
+                    return;
+                }
             }
+        }
+    }
 
-            Err(Parser::error(token, message))
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ParseError> {
+        if let Some(token) = self.check_next_token(|token| token.token_type == token_type) {
+            return Ok(token);
+        }
+
+        if let Some(token) = self.reader.peek() {
+            Err(Self::error(token, message))
         } else {
-            Err(Parser::error(&Token::empty(), "Expected expression."))
+            Err(Self::error(&Token::empty(), "Expected expression."))
         }
     }
 }
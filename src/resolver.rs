@@ -0,0 +1,146 @@
+use crate::{
+    core::{Expr, FunctionDecl, Stmt, Token},
+    error::Diagnostic,
+};
+use std::{cell::Cell, collections::HashMap};
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// Walks the syntax tree once before it is interpreted, binding
+// every local variable to the number of scopes between its use
+// and its declaration, and reporting errors that can be found
+// without running the program. Variables that are not found in
+// any scope are assumed to be globals.
+pub struct Resolver {
+    // Each scope maps a name to whether its initializer has been resolved
+    scopes: Vec<HashMap<String, bool>>,
+    pub(crate) current_function: FunctionType,
+    pub(crate) current_class: ClassType,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Default for Resolver {
+    fn default() -> Resolver {
+        Resolver::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Box<dyn Stmt>]) {
+        for statement in statements {
+            statement.resolve(self);
+        }
+    }
+
+    pub fn resolve_expr(&mut self, expr: &dyn Expr) {
+        expr.resolve(self);
+    }
+
+    pub(crate) fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub(crate) fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    // Adds the name to the innermost scope, marking it as not ready for use
+    pub(crate) fn declare(&mut self, name: &Token) {
+        let redeclared = match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.lexeme.clone(), false).is_some(),
+            None => false,
+        };
+
+        if redeclared {
+            self.report(
+                Diagnostic::new(
+                    name.span,
+                    "Already a variable with this name in this scope.",
+                )
+                .with_help("assign to the existing variable instead of declaring it again"),
+            );
+        }
+    }
+
+    pub(crate) fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    // Defines a name that does not appear in the source, like 'this'
+    pub(crate) fn define_implicit(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+
+    // Returns whether the name is declared in the innermost scope
+    // but its initializer has not been resolved yet
+    pub(crate) fn is_declared_only(&self, name: &Token) -> bool {
+        match self.scopes.last() {
+            Some(scope) => scope.get(&name.lexeme) == Some(&false),
+            None => false,
+        }
+    }
+
+    // Stores the distance to the closest scope declaring the name.
+    // The depth is left empty for globals.
+    pub(crate) fn resolve_local(&mut self, depth: &Cell<Option<usize>>, name: &str) {
+        for (distance, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(name) {
+                depth.set(Some(distance));
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn resolve_function(
+        &mut self,
+        function: &FunctionDecl,
+        function_type: FunctionType,
+    ) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    pub(crate) fn error(&mut self, token: &Token, message: &str) {
+        self.report(Diagnostic::new(token.span, message));
+    }
+
+    pub(crate) fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+}
@@ -1,110 +1,130 @@
-use crate::{
-    core::{NextTokenInfo, SyntaxError, SyntaxResult, Token, TokenType},
-    error,
-};
+use crate::core::{LexError, LexResult, NextTokenInfo, Position, Span, Token, TokenType};
 use std::iter::Peekable;
 use std::str::Chars;
 
+// Splits source code into tokens, one at a time as it is iterated
+// over. Offsets into the source are kept in bytes, so that lexemes
+// can be sliced out of it directly and scanning takes time linear
+// in the size of the source.
+//
+// Bad input is yielded as an error and skipped, and scanning goes
+// on after it. The last item is always an EOF token.
 pub struct Scanner<'a> {
-    source: &'a String,
+    source: &'a str,
     reader: Peekable<Chars<'a>>,
-    tokens: Vec<Token>,
+    // Byte offsets of the start of the current token and of the
+    // next character
     start: usize,
     current: usize,
     line: usize,
+    column: usize,
+    start_position: Position,
+    // A token to yield before scanning any further
+    pending: Option<Token>,
+    reached_end: bool,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(source: &'a String) -> Scanner<'a> {
+    pub fn new(source: &'a str) -> Scanner<'a> {
         let reader = source.chars().peekable();
 
         Scanner {
             source,
             reader,
-            tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_position: Position { line: 1, column: 1 },
+            pending: None,
+            reached_end: false,
         }
     }
 
-    pub fn scan_tokens(&mut self) -> SyntaxResult {
-        while let Some(_) = self.reader.peek() {
-            self.start = self.current;
-            self.scan_token()?;
-        }
-
-        Ok(())
-    }
-
-    pub fn get_tokens(&self) -> &Vec<Token> {
-        &self.tokens
-    }
-
-    // Matches the incoming characters with the corresponding Token
-    fn scan_token(&mut self) -> SyntaxResult {
+    // Matches the incoming characters with the corresponding Token.
+    // Returns None for whitespace and comments.
+    fn scan_token(&mut self) -> Option<LexResult> {
         use TokenType::*;
 
-        match self.advance().unwrap() {
-            '(' => self.add_token(LeftParen),
-            ')' => self.add_token(RightParen),
-            '{' => self.add_token(LeftBrace),
-            '}' => self.add_token(RightBrace),
-            ',' => self.add_token(Comma),
-            '.' => self.add_token(Dot),
-            '-' => self.add_token(Minus),
-            '+' => self.add_token(Plus),
-            ';' => self.add_token(SemiColon),
-            '*' => self.add_token(Star),
-            '!' => self.add_next_token(NextTokenInfo('=', BangEqual, Bang)),
-            '=' => self.add_next_token(NextTokenInfo('=', EqualEqual, Equal)),
-            '<' => self.add_next_token(NextTokenInfo('=', LessEqual, Less)),
-            '>' => self.add_next_token(NextTokenInfo('=', GreaterEqual, Greater)),
+        let token = match self.advance().unwrap() {
+            '(' => self.make_token(LeftParen),
+            ')' => self.make_token(RightParen),
+            '{' => self.make_token(LeftBrace),
+            '}' => self.make_token(RightBrace),
+            ',' => self.make_token(Comma),
+            '.' => self.make_token(Dot),
+            '-' => self.make_token(Minus),
+            '+' => self.make_token(Plus),
+            ';' => self.make_token(SemiColon),
+            '*' => self.make_token(Star),
+            '!' => self.make_next_token(NextTokenInfo('=', BangEqual, Bang)),
+            '=' => self.make_next_token(NextTokenInfo('=', EqualEqual, Equal)),
+            '<' => self.make_next_token(NextTokenInfo('=', LessEqual, Less)),
+            '>' => self.make_next_token(NextTokenInfo('=', GreaterEqual, Greater)),
             '/' => match self.check_next_symbol(|c| c == '/') {
-                None => {
-                    error::error(self.line, "Unexpected EOF");
-                    return Err(SyntaxError {});
-                }
-                Some(false) => self.add_token(Slash),
+                None | Some(false) => self.make_token(Slash),
                 Some(true) => {
                     while let Some(false) = self.check_next_symbol(|c| c == '\n') {
                         self.advance();
                     }
-                    self.line += 1;
+                    return None;
                 }
             },
-            ' ' | '\r' | '\t' => (),
-            '\n' => {
-                self.line += 1;
-            }
-            '"' => {
-                let new_string = self.make_string();
-                if let Ok(s) = new_string {
-                    self.add_token(s);
-                }
-            }
+            ' ' | '\r' | '\t' | '\n' => return None,
+            '"' => return Some(self.make_string()),
             c => {
-                if c.is_digit(10) {
-                    let new_number = self.make_number();
-                    if let Ok(num) = new_number {
-                        self.add_token(num);
-                    }
+                if c.is_ascii_digit() {
+                    return Some(self.make_number());
                 } else if c.is_alphabetic() || c == '_' {
                     let new_id = self.make_identifier();
-                    self.add_token(new_id);
+                    self.make_token(new_id)
                 } else {
-                    error::error(self.line, "Unexpected character.");
-                    return Err(SyntaxError {});
+                    return Some(Err(self.error("Unexpected character.")));
                 }
             }
-        }
+        };
+
+        Some(Ok(token))
+    }
 
-        Ok(())
+    fn error(&self, message: &str) -> LexError {
+        LexError {
+            message: message.to_string(),
+            span: self.span(),
+            label: None,
+            help: None,
+        }
     }
 
     fn advance(&mut self) -> Option<char> {
-        self.current += 1;
-        self.reader.next()
+        let c = self.reader.next()?;
+        self.current += c.len_utf8();
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn current_position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    // The span from the start of the current token up to the next character
+    fn span(&self) -> Span {
+        Span {
+            offset: self.start,
+            len: self.current - self.start,
+            start: self.start_position,
+            end: self.current_position(),
+        }
     }
 
     // Checks the next symbol and if it satisfies a closure, consumes it
@@ -123,47 +143,44 @@ impl<'a> Scanner<'a> {
         None
     }
 
-    fn add_token(&mut self, token_type: TokenType) {
-        let text;
-        if let TokenType::Str(_) = token_type {
-            text = self
-                .source
-                .chars()
-                .skip(self.start + 1)
-                .take((self.current - 1) - (self.start + 1))
-                .collect::<String>();
+    // The source text of the token being scanned
+    fn lexeme(&self) -> &'a str {
+        &self.source[self.start..self.current]
+    }
+
+    // The contents of the string being scanned, without its quotes
+    fn string_contents(&self) -> &'a str {
+        &self.source[self.start + 1..self.current - 1]
+    }
+
+    fn make_token(&self, token_type: TokenType) -> Token {
+        let text = if let TokenType::Str(_) = token_type {
+            self.string_contents()
         } else {
-            text = self
-                .source
-                .chars()
-                .skip(self.start)
-                .take(self.current - self.start)
-                .collect::<String>();
-        }
-        self.tokens.push(Token::new(token_type, text, self.line));
+            self.lexeme()
+        };
+        Token::new(token_type, text.to_string(), self.span())
     }
 
-    // Adds a Token depending upon the next symbol
-    fn add_next_token(&mut self, next_token: NextTokenInfo) {
+    // Makes a Token depending upon the next symbol
+    fn make_next_token(&mut self, next_token: NextTokenInfo) -> Token {
         let NextTokenInfo(expected, token_type1, token_type2) = next_token;
         if let Some(true) = self.check_next_symbol(|c| c == expected) {
-            self.add_token(token_type1);
+            self.make_token(token_type1)
         } else {
-            self.add_token(token_type2);
+            self.make_token(token_type2)
         }
     }
 
-    fn make_string(&mut self) -> Result<TokenType, SyntaxError> {
+    fn make_string(&mut self) -> LexResult {
         loop {
             match self.check_next_symbol(|c| c == '"') {
                 None => {
-                    error::error(self.line, "Unterminated string.");
-                    return Err(SyntaxError {});
+                    let mut error = self.error("Unterminated string.");
+                    error.label = Some("string starts here".to_string());
+                    return Err(error);
                 }
                 Some(false) => {
-                    if let Some(true) = self.check_next_symbol(|c| c == '\n') {
-                        self.line += 1;
-                    }
                     self.advance();
                 }
                 Some(true) => {
@@ -172,26 +189,27 @@ impl<'a> Scanner<'a> {
             }
         }
 
-        let literal_value = self
-            .source
-            .chars()
-            .skip(self.start + 1)
-            .take((self.current - 1) - (self.start + 1))
-            .collect::<String>();
-
-        Ok(TokenType::Str(literal_value))
+        let contents = self.string_contents().to_string();
+        Ok(self.make_token(TokenType::Str(contents)))
     }
 
-    fn make_number(&mut self) -> Result<TokenType, SyntaxError> {
+    // A number with a trailing '.' is an error, but the number is
+    // still yielded after it so that it doesn't confuse the parser
+    fn make_number(&mut self) -> LexResult {
         loop {
-            match self.check_next_symbol(|c| c.is_digit(10)) {
+            match self.check_next_symbol(|c| c.is_ascii_digit()) {
                 Some(false) => {
                     if let Some(true) = self.check_next_symbol(|c| c == '.') {
-                        if let Some(false) | None = self.check_next_symbol(|c| c.is_digit(10)) {
-                            error::error(self.line, "Number cannot end with '.' operator");
-                            return Err(SyntaxError {});
+                        if let Some(false) | None = self.check_next_symbol(|c| c.is_ascii_digit()) {
+                            let mut error = self.error("Number cannot end with '.' operator");
+                            error.help =
+                                Some("add a digit after the '.', such as '1.0'".to_string());
+
+                            let number = TokenType::Number(self.lexeme().parse().unwrap());
+                            self.pending = Some(self.make_token(number));
+                            return Err(error);
                         }
-                        while let Some(true) = self.check_next_symbol(|c| c.is_digit(10)) {}
+                        while let Some(true) = self.check_next_symbol(|c| c.is_ascii_digit()) {}
                     }
                     break;
                 }
@@ -204,29 +222,14 @@ impl<'a> Scanner<'a> {
             }
         }
 
-        let literal_value = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take((self.current) - (self.start))
-            .collect::<String>();
-
-        let literal_value: f64 = literal_value.parse().unwrap();
-
-        Ok(TokenType::Number(literal_value))
+        let number = TokenType::Number(self.lexeme().parse().unwrap());
+        Ok(self.make_token(number))
     }
 
     fn make_identifier(&mut self) -> TokenType {
         while let Some(true) = self.check_next_symbol(|c| c.is_alphanumeric() || c == '_') {}
 
-        let literal_value = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take((self.current) - (self.start))
-            .collect::<String>();
-
-        match literal_value.as_ref() {
+        match self.lexeme() {
             "and" => TokenType::And,
             "class" => TokenType::Class,
             "else" => TokenType::Else,
@@ -247,3 +250,31 @@ impl<'a> Scanner<'a> {
         }
     }
 }
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = LexResult;
+
+    fn next(&mut self) -> Option<LexResult> {
+        if let Some(token) = self.pending.take() {
+            return Some(Ok(token));
+        }
+
+        while self.reader.peek().is_some() {
+            self.start = self.current;
+            self.start_position = self.current_position();
+
+            if let Some(result) = self.scan_token() {
+                return Some(result);
+            }
+        }
+
+        if self.reached_end {
+            return None;
+        }
+
+        self.reached_end = true;
+        self.start = self.current;
+        self.start_position = self.current_position();
+        Some(Ok(Token::new(TokenType::EOF, String::new(), self.span())))
+    }
+}
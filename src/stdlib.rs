@@ -0,0 +1,78 @@
+use crate::{
+    native::{IntoNative, NativeFunction},
+    value::Value,
+};
+use std::{
+    f64::consts,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// The globals defined in every program before it starts
+pub fn globals() -> Vec<(&'static str, Value)> {
+    let start = Instant::now();
+
+    vec![
+        // Seconds since the interpreter was created, from a clock
+        // that never goes backwards, for timing parts of a program
+        native("clock", move || start.elapsed().as_secs_f64()),
+        native("time", time),
+        native("sleep", sleep),
+        // Math, following the rules of floating point numbers, so
+        // that sqrt(-1) is NaN rather than an error
+        ("PI", Value::Number(consts::PI)),
+        ("E", Value::Number(consts::E)),
+        native("sqrt", f64::sqrt),
+        native("pow", f64::powf),
+        native("abs", f64::abs),
+        native("floor", f64::floor),
+        native("ceil", f64::ceil),
+        // Halfway cases are rounded away from zero
+        native("round", f64::round),
+        native("min", f64::min),
+        native("max", f64::max),
+        native("sin", f64::sin),
+        native("cos", f64::cos),
+        native("tan", f64::tan),
+        native("asin", f64::asin),
+        native("acos", f64::acos),
+        native("atan", f64::atan),
+        native("atan2", f64::atan2),
+        // The natural logarithm
+        native("log", f64::ln),
+        native("log10", f64::log10),
+        native("log2", f64::log2),
+        native("exp", f64::exp),
+    ]
+}
+
+fn native<F, Args>(name: &'static str, function: F) -> (&'static str, Value)
+where
+    F: IntoNative<Args>,
+{
+    let native = NativeFunction::from_fn(name, function);
+    (name, Value::Native(Rc::new(native)))
+}
+
+// Seconds since the Unix epoch according to the system clock
+fn time() -> Result<f64, String> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64())
+        .map_err(|_| "The system clock is set before 1970.".to_string())
+}
+
+fn sleep(seconds: f64) -> Result<(), String> {
+    if seconds < 0.0 {
+        return Err("Argument 1 of sleep() must be a non-negative number.".to_string());
+    }
+    if !seconds.is_finite() {
+        return Err("Argument 1 of sleep() must be a finite number.".to_string());
+    }
+    // Only a finite number of seconds too large for a Duration is left
+    let duration = Duration::try_from_secs_f64(seconds)
+        .map_err(|_| "Argument 1 of sleep() is too large.".to_string())?;
+    thread::sleep(duration);
+    Ok(())
+}
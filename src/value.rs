@@ -0,0 +1,161 @@
+use crate::{
+    class::{LoxClass, LoxInstance},
+    function::{Callable, LoxFunction},
+    native::NativeFunction,
+    vm::{BoundMethod, Closure},
+};
+use std::cell::RefCell;
+use std::convert::{Infallible, TryFrom};
+use std::fmt;
+use std::rc::Rc;
+
+// The runtime representation of every value a Lox program can produce
+#[derive(Clone, Debug)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    // Implemented in Rust by the host
+    Native(Rc<NativeFunction>),
+    // Only created by the virtual machine
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+}
+
+impl Value {
+    // nil and false are falsy, everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn as_callable(&self) -> Option<&dyn Callable> {
+        match self {
+            Value::Function(function) => Some(function.as_ref()),
+            Value::Class(class) => Some(class),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    // Values of different types are never equal
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(truth) => write!(f, "{}", truth),
+            Value::Number(num) => write!(f, "{}", num),
+            Value::Str(string) => write!(f, "{}", string),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::Native(native) => write!(f, "{}", native),
+            Value::Closure(closure) => write!(f, "{}", closure),
+            Value::BoundMethod(bound) => write!(f, "{}", bound),
+        }
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Value {
+        Value::Nil
+    }
+}
+
+impl From<bool> for Value {
+    fn from(truth: bool) -> Value {
+        Value::Bool(truth)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(num: f64) -> Value {
+        Value::Number(num)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Value {
+        Value::Str(Rc::from(string))
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Value {
+        Value::Str(Rc::from(string))
+    }
+}
+
+// Why a value couldn't be converted into a Rust type
+#[derive(Debug)]
+pub struct TypeError {
+    // The kind of value that was needed, such as "a number"
+    pub expected: &'static str,
+}
+
+// Converting a value into itself always succeeds
+impl From<Infallible> for TypeError {
+    fn from(never: Infallible) -> TypeError {
+        match never {}
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = TypeError;
+
+    fn try_from(value: Value) -> Result<f64, TypeError> {
+        match value {
+            Value::Number(num) => Ok(num),
+            _ => Err(TypeError {
+                expected: "a number",
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = TypeError;
+
+    fn try_from(value: Value) -> Result<bool, TypeError> {
+        match value {
+            Value::Bool(truth) => Ok(truth),
+            _ => Err(TypeError {
+                expected: "a boolean",
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = TypeError;
+
+    fn try_from(value: Value) -> Result<String, TypeError> {
+        match value {
+            Value::Str(string) => Ok(string.to_string()),
+            _ => Err(TypeError {
+                expected: "a string",
+            }),
+        }
+    }
+}
@@ -0,0 +1,674 @@
+use crate::{
+    chunk::{Chunk, FunctionProto, OpCode},
+    class::{LoxClass, LoxInstance},
+    core::{RuntimeError, MAX_CALL_DEPTH},
+    debug, stdlib,
+    value::Value,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+    rc::Rc,
+};
+
+// A variable captured by a closure. It points into the stack while
+// the variable is still live there, and owns the value afterwards.
+pub(crate) enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Closure {
+    pub(crate) function: Rc<FunctionProto>,
+    pub(crate) upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl fmt::Display for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// A method accessed on an instance, remembering the instance
+// so that it can be bound to 'this' when called
+pub struct BoundMethod {
+    pub(crate) receiver: Value,
+    pub(crate) method: Rc<Closure>,
+}
+
+impl fmt::Display for BoundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.method)
+    }
+}
+
+impl fmt::Debug for BoundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // Index of the first stack slot belonging to this call
+    slots: usize,
+}
+
+type VmResult<T> = Result<T, RuntimeError>;
+
+// A stack based virtual machine executing compiled bytecode.
+// Globals outlive a single call to interpret, so that the REPL
+// can compile and run each line separately.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    // Sorted by the stack slot they point to
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    // Where print statements write to
+    pub output: Box<dyn Write>,
+    // Print the stack and each instruction as it is executed
+    pub trace: bool,
+}
+
+impl Default for Vm {
+    fn default() -> Vm {
+        Vm::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let globals = stdlib::globals()
+            .into_iter()
+            .map(|(name, value)| (Rc::from(name), value))
+            .collect();
+
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+            output: Box::new(io::stdout()),
+            trace: false,
+        }
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(Rc::from(name), value);
+    }
+
+    pub fn interpret(&mut self, function: FunctionProto) -> VmResult<()> {
+        let closure = Rc::new(Closure {
+            function: Rc::new(function),
+            upvalues: Vec::new(),
+        });
+
+        self.stack.push(Value::Closure(Rc::clone(&closure)));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: 0,
+        });
+
+        let result = self.run().map(|_| ());
+        if result.is_err() {
+            self.reset();
+        }
+
+        result
+    }
+
+    // Calls the value from outside of any script, such as a function
+    // looked up by the host after running one
+    pub fn call_function(&mut self, callee: Value, arguments: Vec<Value>) -> VmResult<Value> {
+        let arg_count = arguments.len();
+        self.stack.push(callee);
+        self.stack.extend(arguments);
+
+        let depth = self.frames.len();
+        if let Err(message) = self.call_value(arg_count) {
+            self.reset();
+            return Err(RuntimeError::without_span(&message));
+        }
+
+        // Natives and classes without an initializer return at once,
+        // without pushing a frame for the function
+        if self.frames.len() == depth {
+            return Ok(self.pop());
+        }
+
+        let result = self.run();
+        if result.is_err() {
+            self.reset();
+        }
+
+        result
+    }
+
+    // Abandons the calls that were being run after a runtime error
+    fn reset(&mut self) {
+        self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+    }
+
+    // Runs until the outermost frame returns, giving its return value
+    fn run(&mut self) -> VmResult<Value> {
+        let frame = self.frames.last().expect("No function is being run");
+        let mut closure = Rc::clone(&frame.closure);
+        let mut ip = frame.ip;
+        let mut slots = frame.slots;
+
+        loop {
+            let chunk = &closure.function.chunk;
+            let offset = ip;
+
+            if self.trace {
+                let traced = self
+                    .trace_stack()
+                    .and_then(|()| debug::disassemble_instruction(chunk, offset, &mut self.output));
+                if let Err(error) = traced {
+                    let message = format!("Couldn't write the output: {}.", error);
+                    return Err(self.error(chunk, offset, &message));
+                }
+            }
+
+            let op = OpCode::from_byte(chunk.code[ip]).expect("Invalid opcode");
+            ip += 1;
+
+            match op {
+                OpCode::Constant => {
+                    let index = chunk.read_u16(ip) as usize;
+                    ip += 2;
+                    self.stack.push(chunk.constants[index].clone());
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = chunk.read_u16(ip) as usize;
+                    ip += 2;
+                    self.stack.push(self.stack[slots + slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = chunk.read_u16(ip) as usize;
+                    ip += 2;
+                    self.stack[slots + slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = read_string(chunk, ip);
+                    ip += 2;
+                    match self.globals.get(name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => {
+                            return Err(self.error(
+                                chunk,
+                                offset,
+                                &format!("Undefined variable '{}'.", name),
+                            ))
+                        }
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = read_string(chunk, ip);
+                    ip += 2;
+                    let value = self.pop();
+                    self.globals.insert(Rc::from(name), value);
+                }
+                OpCode::SetGlobal => {
+                    let name = read_string(chunk, ip);
+                    ip += 2;
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(name) {
+                        Some(global) => *global = value,
+                        None => {
+                            return Err(self.error(
+                                chunk,
+                                offset,
+                                &format!("Undefined variable '{}'.", name),
+                            ))
+                        }
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let index = chunk.read_u16(ip) as usize;
+                    ip += 2;
+                    let value = match &*closure.upvalues[index].borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = chunk.read_u16(ip) as usize;
+                    ip += 2;
+                    let value = self.peek(0).clone();
+                    match &mut *closure.upvalues[index].borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = read_string(chunk, ip);
+                    ip += 2;
+                    let instance = match self.peek(0) {
+                        Value::Instance(instance) => Rc::clone(instance),
+                        _ => {
+                            return Err(self.error(
+                                chunk,
+                                offset,
+                                "Only instances have properties.",
+                            ))
+                        }
+                    };
+
+                    let field = instance.borrow().get_field(name);
+                    let value = match field {
+                        Some(value) => value,
+                        None => {
+                            let class = Rc::clone(&instance.borrow().class);
+                            match bind_method(&class, name, Value::Instance(instance)) {
+                                Some(method) => method,
+                                None => {
+                                    return Err(self.error(
+                                        chunk,
+                                        offset,
+                                        &format!("Undefined property '{}'.", name),
+                                    ))
+                                }
+                            }
+                        }
+                    };
+
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let name = read_string(chunk, ip);
+                    ip += 2;
+                    let instance = match self.peek(1) {
+                        Value::Instance(instance) => Rc::clone(instance),
+                        _ => return Err(self.error(chunk, offset, "Only instances have fields.")),
+                    };
+
+                    let value = self.pop();
+                    instance.borrow_mut().set(name, value.clone());
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = read_string(chunk, ip);
+                    ip += 2;
+                    let superclass = match self.pop() {
+                        Value::Class(superclass) => superclass,
+                        _ => unreachable!("'super' is not bound to a class"),
+                    };
+                    let receiver = self.pop();
+
+                    match bind_method(&superclass, name, receiver) {
+                        Some(method) => self.stack.push(method),
+                        None => {
+                            return Err(self.error(
+                                chunk,
+                                offset,
+                                &format!("Undefined property '{}'.", name),
+                            ))
+                        }
+                    }
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.stack.push(Value::Bool(a == b));
+                }
+                OpCode::NotEqual => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.stack.push(Value::Bool(a != b));
+                }
+                OpCode::Greater => self.binary_op(chunk, offset, |a, b| Value::Bool(a > b))?,
+                OpCode::GreaterEqual => {
+                    self.binary_op(chunk, offset, |a, b| Value::Bool(a >= b))?
+                }
+                OpCode::Less => self.binary_op(chunk, offset, |a, b| Value::Bool(a < b))?,
+                OpCode::LessEqual => self.binary_op(chunk, offset, |a, b| Value::Bool(a <= b))?,
+                OpCode::Add => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    match (a, b) {
+                        (Value::Number(a), Value::Number(b)) => {
+                            self.stack.push(Value::Number(a + b))
+                        }
+                        (Value::Str(a), Value::Str(b)) => {
+                            let mut new_string = String::with_capacity(a.len() + b.len());
+                            new_string.push_str(&a);
+                            new_string.push_str(&b);
+                            self.stack.push(Value::from(new_string));
+                        }
+                        _ => {
+                            return Err(self.error(
+                                chunk,
+                                offset,
+                                "Operands must be two numbers or two strings.",
+                            ))
+                        }
+                    }
+                }
+                OpCode::Subtract => self.binary_op(chunk, offset, |a, b| Value::Number(a - b))?,
+                OpCode::Multiply => self.binary_op(chunk, offset, |a, b| Value::Number(a * b))?,
+                OpCode::Divide => self.binary_op(chunk, offset, |a, b| Value::Number(a / b))?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                OpCode::Negate => match self.pop() {
+                    Value::Number(num) => self.stack.push(Value::Number(-num)),
+                    _ => return Err(self.error(chunk, offset, "Operand must be a number.")),
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    if let Err(error) = writeln!(self.output, "{}", value) {
+                        let message = format!("Couldn't write the output: {}.", error);
+                        return Err(self.error(chunk, offset, &message));
+                    }
+                }
+                OpCode::Jump => {
+                    let jump = chunk.read_u16(ip) as usize;
+                    ip += 2 + jump;
+                }
+                OpCode::JumpIfFalse => {
+                    let jump = chunk.read_u16(ip) as usize;
+                    ip += 2;
+                    if !self.peek(0).is_truthy() {
+                        ip += jump;
+                    }
+                }
+                OpCode::Loop => {
+                    let jump = chunk.read_u16(ip) as usize;
+                    ip = ip + 2 - jump;
+                }
+                OpCode::Call => {
+                    let arg_count = chunk.code[ip] as usize;
+                    ip += 1;
+
+                    self.frames.last_mut().expect("No function is being run").ip = ip;
+                    if let Err(message) = self.call_value(arg_count) {
+                        return Err(self.error(chunk, offset, &message));
+                    }
+
+                    let frame = self.frames.last().expect("No function is being run");
+                    closure = Rc::clone(&frame.closure);
+                    ip = frame.ip;
+                    slots = frame.slots;
+                }
+                OpCode::Closure => {
+                    let index = chunk.read_u16(ip) as usize;
+                    ip += 2;
+                    let function = Rc::clone(&chunk.functions[index]);
+
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = chunk.code[ip] == 1;
+                        let index = chunk.read_u16(ip + 1) as usize;
+                        ip += 3;
+
+                        if is_local {
+                            upvalues.push(self.capture_upvalue(slots + index));
+                        } else {
+                            upvalues.push(Rc::clone(&closure.upvalues[index]));
+                        }
+                    }
+
+                    self.stack
+                        .push(Value::Closure(Rc::new(Closure { function, upvalues })));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    self.close_upvalues(slots);
+                    self.frames.pop();
+
+                    if self.frames.is_empty() {
+                        self.stack.truncate(slots);
+                        return Ok(result);
+                    }
+
+                    self.stack.truncate(slots);
+                    self.stack.push(result);
+
+                    let frame = self.frames.last().expect("No function is being run");
+                    closure = Rc::clone(&frame.closure);
+                    ip = frame.ip;
+                    slots = frame.slots;
+                }
+                OpCode::Class => {
+                    let name = read_string(chunk, ip);
+                    let method_count = chunk.read_u16(ip + 2) as usize;
+                    let has_superclass = chunk.code[ip + 4] == 1;
+                    ip += 5;
+
+                    let mut methods = HashMap::new();
+                    for method in self.stack.split_off(self.stack.len() - method_count) {
+                        if let Value::Closure(closure) = &method {
+                            methods.insert(closure.function.name.clone(), method);
+                        }
+                    }
+
+                    let superclass = if has_superclass {
+                        match self.peek(0) {
+                            Value::Class(superclass) => Some(Rc::clone(superclass)),
+                            _ => {
+                                return Err(self.error(
+                                    chunk,
+                                    offset,
+                                    "Superclass must be a class.",
+                                ))
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    let class = LoxClass::new(name.to_string(), superclass, methods);
+                    self.stack.push(Value::Class(Rc::new(class)));
+                }
+            }
+        }
+    }
+
+    // Creates an error for the instruction at the offset, pointing at
+    // the token it was compiled from. The trace is taken from the call
+    // frames, where every frame but the innermost one is stopped at
+    // the call it made.
+    fn error(&self, chunk: &Chunk, offset: usize, message: &str) -> RuntimeError {
+        let mut error = match chunk.token_at(offset) {
+            Some(token) => RuntimeError::new(token, message),
+            None => RuntimeError::without_span(message),
+        };
+        error.set_line(chunk.lines[offset]);
+
+        let innermost = self.frames.len() - 1;
+        for (index, frame) in self.frames.iter().enumerate().rev() {
+            let function = &frame.closure.function;
+            if index != innermost {
+                error.set_line(function.chunk.lines[frame.ip - 1]);
+            }
+            error.add_frame(&function.name);
+        }
+
+        error
+    }
+
+    // Writes the stack to the output, bottom first
+    fn trace_stack(&mut self) -> io::Result<()> {
+        write!(self.output, "          ")?;
+        for value in &self.stack {
+            write!(self.output, "[ {} ]", value)?;
+        }
+        writeln!(self.output)
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("Stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    // Applies an operator that is only defined for two numbers
+    fn binary_op<F>(&mut self, chunk: &Chunk, offset: usize, op: F) -> VmResult<()>
+    where
+        F: Fn(f64, f64) -> Value,
+    {
+        let b = self.pop();
+        let a = self.pop();
+
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => {
+                self.stack.push(op(a, b));
+                Ok(())
+            }
+            _ => Err(self.error(chunk, offset, "Operands must be numbers.")),
+        }
+    }
+
+    // Calls the value sitting below the arguments on the stack.
+    // Returns the error message if it can't be called with them.
+    fn call_value(&mut self, arg_count: usize) -> Result<(), String> {
+        let callee_slot = self.stack.len() - 1 - arg_count;
+
+        match self.stack[callee_slot].clone() {
+            Value::Closure(closure) => self.call(closure, arg_count),
+            Value::BoundMethod(bound) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), arg_count)
+            }
+            Value::Class(class) => match class.find_method("init") {
+                Some(Value::Closure(initializer)) => {
+                    check_arity(initializer.function.arity, arg_count)?;
+
+                    let instance = LoxInstance::new(Rc::clone(&class));
+                    self.stack[callee_slot] = Value::Instance(Rc::new(RefCell::new(instance)));
+                    self.call(initializer, arg_count)
+                }
+                _ => {
+                    check_arity(0, arg_count)?;
+
+                    let instance = LoxInstance::new(Rc::clone(&class));
+                    self.stack[callee_slot] = Value::Instance(Rc::new(RefCell::new(instance)));
+                    Ok(())
+                }
+            },
+            Value::Native(native) => {
+                let arguments = self.stack.split_off(callee_slot + 1);
+                let result = native.call(arguments)?;
+                self.stack[callee_slot] = result;
+                Ok(())
+            }
+            _ => Err("Can only call functions and classes.".to_string()),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, arg_count: usize) -> Result<(), String> {
+        check_arity(closure.function.arity, arg_count)?;
+        // Frames live on the heap, so the VM could go much deeper. It
+        // shares the tree-walker's limit so that both backends agree.
+        if self.frames.len() == MAX_CALL_DEPTH {
+            return Err("Stack overflow.".to_string());
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - 1 - arg_count,
+        });
+        Ok(())
+    }
+
+    // Reuses the open upvalue pointing at the slot, so that all
+    // closures capturing a variable share it
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let position =
+            self.open_upvalues
+                .binary_search_by_key(&slot, |upvalue| match &*upvalue.borrow() {
+                    Upvalue::Open(slot) => *slot,
+                    Upvalue::Closed(_) => usize::MAX,
+                });
+
+        match position {
+            Ok(index) => Rc::clone(&self.open_upvalues[index]),
+            Err(index) => {
+                let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+                self.open_upvalues.insert(index, Rc::clone(&upvalue));
+                upvalue
+            }
+        }
+    }
+
+    // Moves the variables at or above the slot off the stack and
+    // into the upvalues capturing them
+    fn close_upvalues(&mut self, last: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let slot = match &*upvalue.borrow() {
+                Upvalue::Open(slot) => *slot,
+                Upvalue::Closed(_) => usize::MAX,
+            };
+            if slot < last {
+                break;
+            }
+
+            let upvalue = self.open_upvalues.pop().expect("Upvalue disappeared");
+            let value = self.stack[slot].clone();
+            *upvalue.borrow_mut() = Upvalue::Closed(value);
+        }
+    }
+}
+
+fn read_string(chunk: &Chunk, offset: usize) -> &str {
+    match &chunk.constants[chunk.read_u16(offset) as usize] {
+        Value::Str(string) => string,
+        _ => panic!("Expected a string constant"),
+    }
+}
+
+// Creates a bound method for the method of the class, if it exists
+fn bind_method(class: &LoxClass, name: &str, receiver: Value) -> Option<Value> {
+    match class.find_method(name) {
+        Some(Value::Closure(method)) => Some(Value::BoundMethod(Rc::new(BoundMethod {
+            receiver,
+            method,
+        }))),
+        _ => None,
+    }
+}
+
+fn check_arity(arity: usize, arg_count: usize) -> Result<(), String> {
+    if arity != arg_count {
+        return Err(format!(
+            "Expected {} arguments but got {}.",
+            arity, arg_count
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+// Checks how the lox binary reports being used incorrectly. Problems
+// go to stderr, with the exit codes from sysexits.h.
+
+use std::{env, fs, process::Command};
+
+const EX_USAGE: i32 = 64;
+const EX_NOINPUT: i32 = 66;
+
+// Runs the binary, returning its exit code, stdout and stderr
+fn lox(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .output()
+        .expect("Couldn't run the interpreter");
+
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn unknown_options_are_usage_errors() {
+    let (code, stdout, stderr) = lox(&["--fast", "script.lox"]);
+
+    assert_eq!(code, Some(EX_USAGE));
+    assert_eq!(stdout, "");
+    assert!(
+        stderr.starts_with("Unknown option: --fast\nUsage: lox"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn more_than_one_script_is_a_usage_error() {
+    let (code, stdout, stderr) = lox(&["first.lox", "second.lox"]);
+
+    assert_eq!(code, Some(EX_USAGE));
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("Usage: lox"), "{}", stderr);
+}
+
+#[test]
+fn missing_scripts_are_reported() {
+    let path = env::temp_dir().join(format!("lox_cli_missing_{}.lox", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let path = path.to_str().expect("Temporary path isn't valid UTF-8");
+
+    let (code, stdout, stderr) = lox(&[path]);
+
+    assert_eq!(code, Some(EX_NOINPUT));
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, format!("Couldn't find: {}\n", path));
+}
@@ -0,0 +1,439 @@
+// Runs the annotated Lox scripts under tests/lox against both
+// backends, in the format of the Crafting Interpreters test suite:
+//
+//   print 1;               // expect: 1
+//   print nil + 1;         // expect runtime error: Operands must be numbers.
+//   var a = ;              // Error at ';': Expect expression.
+//   // [line 3] Error at end: Expect '}' after block.
+//
+// An error annotation without a line number refers to the line it
+// is written on. Annotations starting with [java line N] only apply
+// to the tree-walker and [c line N] only to the virtual machine, as
+// the two reference implementations report some errors differently.
+//
+// Where this implementation deliberately behaves differently from
+// the reference ones, the reference annotation is kept but tagged
+// with [reference] so that it is skipped, and the behaviour expected
+// here is given by annotations tagged with [lox]:
+//
+//   123.; // [reference] Error at ';': Expect property name after '.'.
+//   // [lox line 1] Error at '123.': Number cannot end with '.' operator
+//
+// Error messages that are only worded differently are translated by
+// MESSAGE_DIVERGENCES rather than rewritten in the scripts.
+//
+// The stdout, the errors reported on stderr and the exit code of
+// every script are compared with its annotations, including the
+// token each error is reported at. All mismatches are collected so
+// that one run shows every failing script.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const EX_DATAERR: i32 = 65;
+const EX_SOFTWARE: i32 = 70;
+
+// Reference error messages and how this implementation words them
+const MESSAGE_DIVERGENCES: &[(&str, &str)] = &[("Expect expression.", "Expected expression.")];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    TreeWalk,
+    Vm,
+}
+
+impl Backend {
+    fn args(self) -> &'static [&'static str] {
+        match self {
+            Backend::TreeWalk => &[],
+            Backend::Vm => &["--vm"],
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Backend::TreeWalk => "tree-walker",
+            Backend::Vm => "vm",
+        }
+    }
+}
+
+// Where an error is reported, as written in an annotation
+#[derive(Clone, PartialEq)]
+enum Location {
+    // At the token with this lexeme
+    Token(String),
+    // At the end of the source
+    End,
+    // Annotations without a location, such as those of runtime
+    // errors, match an error reported anywhere on their line
+    Any,
+}
+
+struct ExpectedError {
+    line: usize,
+    location: Location,
+    message: String,
+}
+
+// An error found on stderr, with the source text its carets point
+// at, if the line was quoted
+struct ActualError {
+    line: usize,
+    lexeme: Option<String>,
+    message: String,
+}
+
+impl ExpectedError {
+    fn matches(&self, actual: &ActualError) -> bool {
+        let location_matches = match (&self.location, &actual.lexeme) {
+            (Location::Any, _) => true,
+            (Location::End, Some(lexeme)) => lexeme.is_empty(),
+            (Location::Token(expected), Some(lexeme)) => expected == lexeme,
+            (_, None) => false,
+        };
+        location_matches && self.line == actual.line && self.message == actual.message
+    }
+}
+
+impl fmt::Display for ExpectedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.location {
+            Location::Token(lexeme) => write!(f, "[line {}] at '{}': ", self.line, lexeme)?,
+            Location::End => write!(f, "[line {}] at end: ", self.line)?,
+            Location::Any => write!(f, "[line {}] ", self.line)?,
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Display for ActualError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.lexeme.as_deref() {
+            Some("") => write!(f, "[line {}] at end: ", self.line)?,
+            Some(lexeme) => write!(f, "[line {}] at '{}': ", self.line, lexeme)?,
+            None => write!(f, "[line {}] ", self.line)?,
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+// What a script is expected to do when run with one backend
+struct Expectations {
+    output: Vec<String>,
+    // In the order the errors are reported
+    errors: Vec<ExpectedError>,
+    exit_code: i32,
+    // Whether the script marks where it diverges from the reference
+    reference_tags: bool,
+    lox_tags: bool,
+}
+
+fn parse_expectations(source: &str, backend: Backend) -> Expectations {
+    let mut expectations = Expectations {
+        output: Vec::new(),
+        errors: Vec::new(),
+        exit_code: 0,
+        reference_tags: false,
+        lox_tags: false,
+    };
+
+    for (index, line) in source.lines().enumerate() {
+        let comment = match line.find("//") {
+            Some(start) => line[start + 2..].trim(),
+            None => continue,
+        };
+        let annotation = match parse_tag(comment, index + 1, backend) {
+            Some(annotation) => annotation,
+            None => continue,
+        };
+        match annotation.tag {
+            Some(Tag::Reference) => {
+                expectations.reference_tags = true;
+                continue;
+            }
+            Some(Tag::Lox) => expectations.lox_tags = true,
+            None => (),
+        }
+
+        // Only the messages of reference annotations need translating
+        let translate = |message: &str| match annotation.tag {
+            Some(Tag::Lox) => message.to_string(),
+            _ => MESSAGE_DIVERGENCES
+                .iter()
+                .find(|(reference, _)| *reference == message)
+                .map_or(message, |(_, ours)| ours)
+                .to_string(),
+        };
+
+        let rest = annotation.rest;
+        if let Some(value) = rest.strip_prefix("expect: ") {
+            expectations.output.push(value.to_string());
+        } else if let Some(message) = rest.strip_prefix("expect runtime error: ") {
+            expectations.errors.push(ExpectedError {
+                line: annotation.line,
+                location: Location::Any,
+                message: translate(message),
+            });
+            expectations.exit_code = EX_SOFTWARE;
+        } else if let Some((location, message)) = parse_error(rest) {
+            expectations.errors.push(ExpectedError {
+                line: annotation.line,
+                location,
+                message: translate(message),
+            });
+            expectations.exit_code = EX_DATAERR;
+        }
+    }
+
+    expectations
+}
+
+enum Tag {
+    Reference,
+    Lox,
+}
+
+// An annotation with its leading "[...]" taken off
+struct Annotation<'a> {
+    tag: Option<Tag>,
+    line: usize,
+    rest: &'a str,
+}
+
+// Takes off a leading tag such as "[c line 3]" or "[lox]", returning
+// None if the annotation is only for the other backend
+fn parse_tag(comment: &str, line: usize, backend: Backend) -> Option<Annotation<'_>> {
+    let mut annotation = Annotation {
+        tag: None,
+        line,
+        rest: comment,
+    };
+    if !comment.starts_with('[') {
+        return Some(annotation);
+    }
+
+    let end = comment.find(']')?;
+    let mut words = comment[1..end].split_whitespace().collect::<Vec<_>>();
+    annotation.rest = comment[end + 1..].trim_start();
+
+    match words.first() {
+        Some(&"java") if backend != Backend::TreeWalk => return None,
+        Some(&"c") if backend != Backend::Vm => return None,
+        Some(&"reference") => annotation.tag = Some(Tag::Reference),
+        Some(&"lox") => annotation.tag = Some(Tag::Lox),
+        _ => (),
+    }
+    if matches!(words.first(), Some(&"java" | &"c" | &"reference" | &"lox")) {
+        words.remove(0);
+    }
+
+    match words.as_slice() {
+        [] => (),
+        ["line", number] => annotation.line = number.parse().ok()?,
+        _ => return None,
+    }
+    Some(annotation)
+}
+
+// Parses the rest of an annotation such as "Error at 'x': message"
+fn parse_error(rest: &str) -> Option<(Location, &str)> {
+    let rest = rest.strip_prefix("Error")?;
+
+    if let Some(location) = rest.strip_prefix(" at '") {
+        let end = location.find("': ")?;
+        let lexeme = location[..end].to_string();
+        Some((Location::Token(lexeme), &location[end + 3..]))
+    } else if let Some(message) = rest.strip_prefix(" at end: ") {
+        Some((Location::End, message))
+    } else {
+        Some((Location::Any, rest.strip_prefix(": ")?))
+    }
+}
+
+// Collects every diagnostic in the stderr of the interpreter. Each
+// starts with "error: message", followed by a line pointing at
+// "path:line:column" and usually the quoted source line, with carets
+// under the text the error is reported at. That text is taken from
+// the source, as the carets line up with it however it is indented.
+fn parse_errors(stderr: &str, source: &str) -> Vec<ActualError> {
+    let source_lines: Vec<&str> = source.split('\n').collect();
+    let mut errors = Vec::new();
+    let mut lines = stderr.lines();
+
+    while let Some(line) = lines.next() {
+        let message = match line.strip_prefix("error: ") {
+            Some(message) => message,
+            None => continue,
+        };
+
+        let position = lines
+            .next()
+            .and_then(|location| location.trim().strip_prefix("--> "))
+            .and_then(|location| {
+                let mut parts = location.rsplit(':');
+                let column = parts.next()?.parse::<usize>().ok()?;
+                let line = parts.next()?.parse::<usize>().ok()?;
+                Some((line, column))
+            });
+        let (line_number, column) = position.unwrap_or((0, 0));
+
+        // The quoted line comes after an empty gutter line, and the
+        // carets right after it
+        let width = lines
+            .clone()
+            .nth(2)
+            .and_then(|carets| carets.split_once('|'))
+            .map(|(_, carets)| {
+                carets
+                    .trim_start()
+                    .chars()
+                    .take_while(|&c| c == '^')
+                    .count()
+            })
+            .filter(|&width| width > 0);
+
+        let lexeme = match (width, source_lines.get(line_number.wrapping_sub(1))) {
+            (Some(width), Some(text)) => Some(
+                text.trim_end_matches('\r')
+                    .chars()
+                    .skip(column.saturating_sub(1))
+                    .take(width)
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        errors.push(ActualError {
+            line: line_number,
+            lexeme,
+            message: message.to_string(),
+        });
+    }
+
+    errors
+}
+
+// Runs the script and describes how it differs from its
+// annotations, if it does
+fn check(path: &Path, backend: Backend) -> Option<String> {
+    let source = fs::read_to_string(path).expect("Couldn't read the test script");
+    let expected = parse_expectations(&source, backend);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(backend.args())
+        .arg(path)
+        .output()
+        .expect("Couldn't run the interpreter");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let actual_output: Vec<&str> = stdout.lines().collect();
+    let actual_errors = parse_errors(&stderr, &source);
+    let exit_code = output.status.code().unwrap_or(-1);
+
+    let mut problems = Vec::new();
+
+    if actual_output != expected.output {
+        problems.push(format!(
+            "  expected output:\n{}\n  got:\n{}",
+            indent(&expected.output),
+            indent(&actual_output)
+        ));
+    }
+
+    let errors_match = actual_errors.len() == expected.errors.len()
+        && expected
+            .errors
+            .iter()
+            .zip(&actual_errors)
+            .all(|(expected, actual)| expected.matches(actual));
+    if !errors_match {
+        let expected_errors: Vec<String> =
+            expected.errors.iter().map(ToString::to_string).collect();
+        let actual_errors: Vec<String> = actual_errors.iter().map(ToString::to_string).collect();
+        problems.push(format!(
+            "  expected errors:\n{}\n  got:\n{}",
+            indent(&expected_errors),
+            indent(&actual_errors)
+        ));
+    }
+
+    if expected.reference_tags != expected.lox_tags {
+        problems.push("  a divergence needs both [reference] and [lox] annotations".to_string());
+    }
+
+    if exit_code != expected.exit_code {
+        problems.push(format!(
+            "  expected exit code {} but got {}",
+            expected.exit_code, exit_code
+        ));
+    }
+
+    if problems.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "{} ({}):\n{}",
+        path.display(),
+        backend.name(),
+        problems.join("\n")
+    ))
+}
+
+fn indent<T: AsRef<str>>(lines: &[T]) -> String {
+    lines
+        .iter()
+        .map(|line| format!("    {}", line.as_ref()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    let entries = fs::read_dir(dir).expect("Couldn't read the test directory");
+
+    for entry in entries {
+        let path = entry.expect("Couldn't read a test directory entry").path();
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            scripts.push(path);
+        }
+    }
+}
+
+fn run_suite(backend: Backend) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("lox");
+    let mut scripts = Vec::new();
+    collect_scripts(&root, &mut scripts);
+    scripts.sort();
+
+    let failures: Vec<String> = scripts
+        .iter()
+        .filter_map(|script| check(script, backend))
+        .collect();
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} scripts failed:\n\n{}",
+        failures.len(),
+        scripts.len(),
+        failures.join("\n\n")
+    );
+}
+
+#[test]
+fn tree_walker_conformance() {
+    run_suite(Backend::TreeWalk);
+}
+
+#[test]
+fn vm_conformance() {
+    run_suite(Backend::Vm);
+}
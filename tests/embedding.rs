@@ -0,0 +1,407 @@
+// Exposes Rust functions to scripts through the library
+
+use lox::{
+    CollectingOutput, CollectingReporter, Lox, LoxError, Options, Source, Value, STACK_SIZE,
+};
+use std::{cell::RefCell, rc::Rc, thread};
+
+fn backends() -> Vec<Lox> {
+    vec![
+        Lox::default(),
+        Lox::new(Options {
+            vm: true,
+            ..Options::default()
+        }),
+    ]
+}
+
+// Defines a native that records every value it is called with
+fn recorder(lox: &mut Lox) -> Rc<RefCell<Vec<Value>>> {
+    let recorded = Rc::new(RefCell::new(Vec::new()));
+    let sink = Rc::clone(&recorded);
+    lox.define_native("record", move |value: Value| sink.borrow_mut().push(value));
+    recorded
+}
+
+#[test]
+fn natives_convert_arguments_and_results() {
+    for mut lox in backends() {
+        let recorded = recorder(&mut lox);
+        lox.define_native("add", |a: f64, b: f64| a + b);
+        lox.define_native("shout", |s: String| s.to_uppercase() + "!");
+        lox.define_native("answer", || 42.0);
+        lox.define_native("both", |a: bool, b: bool| a && b);
+
+        let source = r#"
+            record(add(1, 2));
+            record(shout("hi"));
+            record(answer());
+            record(both(true, false));
+            record(record);
+        "#;
+        assert_eq!(lox.run("natives", source), Ok(()));
+
+        let recorded = recorded.borrow();
+        assert_eq!(
+            recorded[..4],
+            [
+                Value::from(3.0),
+                Value::from("HI!"),
+                Value::from(42.0),
+                Value::from(false),
+            ]
+        );
+        assert_eq!(recorded[4].to_string(), "<native fn>");
+    }
+}
+
+#[test]
+fn natives_can_be_passed_around_and_called_from_functions() {
+    for mut lox in backends() {
+        let recorded = recorder(&mut lox);
+        lox.define_native("double", |n: f64| n * 2.0);
+
+        let source = r#"
+            fun apply(f, x) { return f(x); }
+            var twice = double;
+            record(apply(twice, 4));
+        "#;
+        assert_eq!(lox.run("passing", source), Ok(()));
+        assert_eq!(*recorded.borrow(), [Value::from(8.0)]);
+    }
+}
+
+#[test]
+fn wrong_argument_counts_and_types_are_runtime_errors() {
+    for mut lox in backends() {
+        lox.define_native("square", |n: f64| n * n);
+
+        assert_eq!(lox.run("count", "square(1, 2);"), Err(LoxError::Runtime));
+        assert_eq!(lox.run("type", "square(\"4\");"), Err(LoxError::Runtime));
+        assert_eq!(lox.run("ok", "square(4);"), Ok(()));
+    }
+}
+
+#[test]
+fn errors_returned_by_natives_stop_the_script() {
+    for mut lox in backends() {
+        let recorded = recorder(&mut lox);
+        lox.define_native("check", |n: f64| {
+            if n < 0.0 {
+                Err("Expected a positive number.".to_string())
+            } else {
+                Ok(n)
+            }
+        });
+
+        let source = r#"
+            record(check(1));
+            record(check(-1));
+            record("unreachable");
+        "#;
+        assert_eq!(lox.run("failing", source), Err(LoxError::Runtime));
+        assert_eq!(*recorded.borrow(), [Value::from(1.0)]);
+    }
+}
+
+#[test]
+fn host_calls_functions_defined_by_a_script() {
+    for mut lox in backends() {
+        let source = r#"
+            fun add(a, b) { return a + b; }
+            fun greet(name) { return "Hello, " + name + "!"; }
+            fun nothing() {}
+            fun makeAdder(n) {
+                fun adder(x) { return x + n; }
+                return adder;
+            }
+        "#;
+        assert_eq!(lox.run("functions", source), Ok(()));
+
+        let sum = lox.call_function("add", vec![Value::from(1.0), Value::from(2.0)]);
+        assert_eq!(sum.unwrap(), Value::from(3.0));
+
+        let greeting = lox.call_function("greet", vec![Value::from("Lox")]);
+        assert_eq!(greeting.unwrap(), Value::from("Hello, Lox!"));
+
+        assert_eq!(lox.call_function("nothing", vec![]).unwrap(), Value::Nil);
+
+        // Closures returned to the host can be called later
+        let adder = lox.call_function("makeAdder", vec![Value::from(10.0)]);
+        let result = lox.call(&adder.unwrap(), vec![Value::from(5.0)]);
+        assert_eq!(result.unwrap(), Value::from(15.0));
+    }
+}
+
+#[test]
+fn host_calls_classes_and_natives() {
+    for mut lox in backends() {
+        lox.define_native("negate", |n: f64| -n);
+        let source = r#"
+            class Point {
+                init(x, y) { this.x = x; this.y = y; }
+                sum() { return this.x + this.y; }
+            }
+            class Empty {}
+            var point;
+        "#;
+        assert_eq!(lox.run("classes", source), Ok(()));
+
+        let point = lox.call_function("Point", vec![Value::from(1.0), Value::from(2.0)]);
+        lox.set_global("point", point.unwrap());
+        assert_eq!(lox.run("method", "var total = point.sum();"), Ok(()));
+        assert_eq!(lox.get_global("total"), Some(Value::from(3.0)));
+
+        let empty = lox.call_function("Empty", vec![]).unwrap();
+        assert_eq!(empty.to_string(), "Empty instance");
+
+        let negated = lox.call_function("negate", vec![Value::from(4.0)]);
+        assert_eq!(negated.unwrap(), Value::from(-4.0));
+    }
+}
+
+#[test]
+fn host_reads_and_writes_globals() {
+    for mut lox in backends() {
+        lox.set_global("limit", 10.0);
+        lox.set_global("name", "config");
+        let source = r#"
+            var doubled = limit * 2;
+            var label = name + ".lox";
+            var flag = true;
+        "#;
+        assert_eq!(lox.run("globals", source), Ok(()));
+
+        assert_eq!(lox.get_global("doubled"), Some(Value::from(20.0)));
+        assert_eq!(lox.get_global("label"), Some(Value::from("config.lox")));
+        assert_eq!(lox.get_global("flag"), Some(Value::from(true)));
+        assert_eq!(lox.get_global("missing"), None);
+
+        lox.set_global("flag", false);
+        assert_eq!(lox.get_global("flag"), Some(Value::from(false)));
+    }
+}
+
+#[test]
+fn classes_with_an_invalid_superclass_are_not_defined() {
+    for mut lox in backends() {
+        let (_, reporter) = capture(&mut lox);
+        assert_eq!(
+            lox.run("class", "var B = 1; class A < B {}"),
+            Err(LoxError::Runtime)
+        );
+        assert_eq!(lox.get_global("A"), None);
+
+        assert_eq!(lox.run("use", "print A;"), Err(LoxError::Runtime));
+        let reports = reporter.reports();
+        assert!(reports[0].starts_with("error: Superclass must be a class.\n"));
+        assert!(reports[1].starts_with("error: Undefined variable 'A'.\n"));
+    }
+}
+
+#[test]
+fn host_calls_return_runtime_errors() {
+    for mut lox in backends() {
+        let source = r#"
+            fun fail() { return nil + 1; }
+            fun one(a) { return a; }
+            var number = 1;
+        "#;
+        assert_eq!(lox.run("errors", source), Ok(()));
+
+        let error = lox.call_function("fail", vec![]).unwrap_err();
+        assert_eq!(
+            error.message,
+            "Operands must be two numbers or two strings."
+        );
+        let error = lox.call_function("one", vec![]).unwrap_err();
+        assert_eq!(error.message, "Expected 1 arguments but got 0.");
+        let error = lox.call_function("number", vec![]).unwrap_err();
+        assert_eq!(error.message, "Can only call functions and classes.");
+        let error = lox.call_function("missing", vec![]).unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'missing'.");
+
+        // The interpreter is still usable after an error
+        let result = lox.call_function("one", vec![Value::from(7.0)]);
+        assert_eq!(result.unwrap(), Value::from(7.0));
+    }
+}
+
+#[test]
+fn host_call_errors_point_at_the_source_only_when_it_failed() {
+    for mut lox in backends() {
+        let text = "fun fail() {\n  return nil + 1;\n}\nfun outer() { fail(); }\nvar number = 1;\n";
+        assert_eq!(lox.run("host.lox", text), Ok(()));
+        let source = Source {
+            name: "host.lox",
+            text,
+        };
+        let mut render = |name: &str, arguments: Vec<Value>| {
+            let error = lox.call_function(name, arguments).unwrap_err();
+            error.diagnostic().render(&source, false)
+        };
+
+        // Errors making the call itself have no place in the source
+        assert_eq!(
+            render("missing", vec![]),
+            "error: Undefined variable 'missing'.\n"
+        );
+        assert_eq!(
+            render("number", vec![]),
+            "error: Can only call functions and classes.\n"
+        );
+        assert_eq!(
+            render("clock", vec![Value::Nil]),
+            "error: Expected 0 arguments but got 1.\n"
+        );
+
+        assert_eq!(
+            render("outer", vec![]),
+            "error: Operands must be two numbers or two strings.\n \
+             --> host.lox:2:14\n  \
+             |\n\
+             2 |   return nil + 1;\n  \
+             |              ^\n\
+             [line 2] in fail()\n\
+             [line 4] in outer()\n"
+        );
+    }
+}
+
+#[test]
+fn host_calls_recursing_without_end_overflow_the_stack() {
+    // Test threads are too small for the tree-walker to reach the limit
+    let test = thread::Builder::new().stack_size(STACK_SIZE).spawn(|| {
+        for mut lox in backends() {
+            let source = r#"
+                fun forever(n) { return forever(n + 1); }
+                fun countdown(n) { if (n > 0) return countdown(n - 1); return "done"; }
+            "#;
+            assert_eq!(lox.run("overflow", source), Ok(()));
+
+            let error = lox
+                .call_function("forever", vec![Value::from(0.0)])
+                .unwrap_err();
+            assert_eq!(error.message, "Stack overflow.");
+
+            // Unwinding gives the frames back for the next call, and
+            // without a script below it the host can make every call
+            let result = lox.call_function("countdown", vec![Value::from(9999.0)]);
+            assert_eq!(result.unwrap(), Value::from("done"));
+            let error = lox
+                .call_function("countdown", vec![Value::from(10000.0)])
+                .unwrap_err();
+            assert_eq!(error.message, "Stack overflow.");
+        }
+    });
+    test.unwrap().join().unwrap();
+}
+
+// Captures what each backend prints and reports
+fn capture(lox: &mut Lox) -> (CollectingOutput, CollectingReporter) {
+    let output = CollectingOutput::new();
+    let reporter = CollectingReporter::new();
+    lox.set_output(output.clone());
+    lox.set_reporter(reporter.clone());
+    (output, reporter)
+}
+
+#[test]
+fn print_writes_to_the_output() {
+    for mut lox in backends() {
+        let (output, reporter) = capture(&mut lox);
+        let source = r#"
+            print "one";
+            print 1 + 1;
+            print nil;
+        "#;
+        assert_eq!(lox.run("print", source), Ok(()));
+        assert_eq!(lox.run_repl("print", "3 * 3"), Ok(()));
+
+        assert_eq!(output.contents(), "one\n2\nnil\n9\n");
+        assert!(reporter.reports().is_empty());
+    }
+}
+
+#[test]
+fn syntax_errors_go_to_the_reporter() {
+    for mut lox in backends() {
+        let (output, reporter) = capture(&mut lox);
+        let source = "print 1;\nvar a = ;\nprint 2 +;\n";
+        assert_eq!(lox.run("syntax.lox", source), Err(LoxError::Parse));
+
+        assert_eq!(output.contents(), "");
+        assert_eq!(
+            reporter.reports(),
+            [
+                "error: Expected expression.\n \
+                 --> syntax.lox:2:9\n  \
+                 |\n\
+                 2 | var a = ;\n  \
+                 |         ^\n",
+                "error: Expected expression.\n \
+                 --> syntax.lox:3:10\n  \
+                 |\n\
+                 3 | print 2 +;\n  \
+                 |          ^\n",
+            ]
+        );
+    }
+}
+
+#[test]
+fn runtime_errors_go_to_the_reporter_with_a_trace() {
+    for mut lox in backends() {
+        let (output, reporter) = capture(&mut lox);
+        let source = "fun f() {\n  return -nil;\n}\nprint \"before\";\nf();\n";
+        assert_eq!(lox.run("runtime.lox", source), Err(LoxError::Runtime));
+
+        assert_eq!(output.contents(), "before\n");
+        assert_eq!(
+            reporter.reports(),
+            ["error: Operand must be a number.\n \
+              --> runtime.lox:2:10\n  \
+              |\n\
+              2 |   return -nil;\n  \
+              |          ^\n\
+              [line 2] in f()\n\
+              [line 5] in script\n"]
+        );
+    }
+}
+
+#[test]
+fn disassembly_and_traces_go_to_the_output() {
+    let mut lox = Lox::new(Options {
+        vm: true,
+        disassemble: true,
+        trace: true,
+    });
+    let (output, reporter) = capture(&mut lox);
+    assert_eq!(lox.run("debug", "print 1 + 2;"), Ok(()));
+
+    assert_eq!(
+        output.contents(),
+        "== <script> ==\n\
+         0000    1 Constant            0 '1'\n\
+         0003    | Constant            1 '2'\n\
+         0006    | Add\n\
+         0007    | Print\n\
+         0008    | Nil\n\
+         0009    | Return\n          \
+         [ <script> ]\n\
+         0000    1 Constant            0 '1'\n          \
+         [ <script> ][ 1 ]\n\
+         0003    | Constant            1 '2'\n          \
+         [ <script> ][ 1 ][ 2 ]\n\
+         0006    | Add\n          \
+         [ <script> ][ 3 ]\n\
+         0007    | Print\n\
+         3\n          \
+         [ <script> ]\n\
+         0008    | Nil\n          \
+         [ <script> ][ nil ]\n\
+         0009    | Return\n"
+    );
+    assert!(reporter.reports().is_empty());
+}
@@ -0,0 +1,126 @@
+// Uses the interpreter as a library rather than through the binary
+
+use lox::{
+    ast::Stmt, Callable, CollectingReporter, Compiler, FunctionProto, Lox, LoxClass, LoxError,
+    LoxFunction, Options, Parser, Position, Scanner, TokenType, Value, Vm,
+};
+use std::rc::Rc;
+
+fn parse(source: &str) -> Vec<Box<dyn Stmt>> {
+    let tokens = Scanner::new(source).map(Result::unwrap);
+    match Parser::new(tokens).parse() {
+        Ok(statements) => statements,
+        Err(_) => panic!("Couldn't parse {:?}", source),
+    }
+}
+
+#[test]
+fn scanner_yields_tokens_ending_with_eof() {
+    let types: Vec<TokenType> = Scanner::new("var a = 1;")
+        .map(|result| result.unwrap().token_type)
+        .collect();
+
+    assert_eq!(
+        types,
+        vec![
+            TokenType::Var,
+            TokenType::Identifier,
+            TokenType::Equal,
+            TokenType::Number(1.0),
+            TokenType::SemiColon,
+            TokenType::EOF,
+        ]
+    );
+}
+
+#[test]
+fn parser_builds_a_statement_per_declaration() {
+    let statements = parse("var a = 1; print a; fun f() {}");
+    assert_eq!(statements.len(), 3);
+}
+
+#[test]
+fn globals_persist_between_runs() {
+    for vm in &[false, true] {
+        let mut lox = Lox::new(Options {
+            vm: *vm,
+            ..Options::default()
+        });
+        assert_eq!(lox.run("first", "var a = 1;"), Ok(()));
+        assert_eq!(lox.run("second", "a = a + 1;"), Ok(()));
+    }
+}
+
+#[test]
+fn compiled_scripts_run_on_a_vm_built_by_hand() {
+    let function: FunctionProto = Compiler::new().compile(&parse("var a = 1 + 2;")).unwrap();
+    let mut vm = Vm::new();
+    assert!(vm.interpret(function).is_ok());
+    assert_eq!(vm.get_global("a"), Some(Value::from(3.0)));
+}
+
+#[test]
+fn functions_and_classes_can_be_inspected_by_the_host() {
+    let mut lox = Lox::default();
+    let source = "fun add(a, b) { return a + b; } class Point { init(x, y) {} }";
+    assert_eq!(lox.run("values", source), Ok(()));
+
+    let function: Rc<LoxFunction> = match lox.get_global("add") {
+        Some(Value::Function(function)) => function,
+        other => panic!("Expected a function, got {:?}", other),
+    };
+    let class: Rc<LoxClass> = match lox.get_global("Point") {
+        Some(Value::Class(class)) => class,
+        other => panic!("Expected a class, got {:?}", other),
+    };
+    assert_eq!(function.arity(), 2);
+    assert_eq!(class.arity(), 2);
+}
+
+#[test]
+fn run_reports_the_stage_that_failed() {
+    let mut lox = Lox::default();
+    assert_eq!(lox.run("syntax", "var a = @;"), Err(LoxError::Syntax));
+    assert_eq!(lox.run("parse", "var a = ;"), Err(LoxError::Parse));
+    assert_eq!(lox.run("resolve", "return 1;"), Err(LoxError::Resolve));
+    assert_eq!(lox.run("runtime", "nil + 1;"), Err(LoxError::Runtime));
+}
+
+#[test]
+fn parser_recovers_inside_a_block_without_eating_its_brace() {
+    let source = "fun f() {\n  print 1 +\n}\nprint 2;";
+    let tokens = Scanner::new(source).map(Result::unwrap);
+
+    let diagnostics = match Parser::new(tokens).parse() {
+        Ok(_) => panic!("Expected a syntax error"),
+        Err(diagnostics) => diagnostics,
+    };
+    let errors: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.position(), diagnostic.message()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![(
+            Some(Position { line: 3, column: 1 }),
+            "Expected expression."
+        )]
+    );
+}
+
+#[test]
+fn a_full_chunk_is_reported_once() {
+    let mut lox = Lox::new(Options {
+        vm: true,
+        ..Options::default()
+    });
+    let reporter = CollectingReporter::new();
+    lox.set_reporter(reporter.clone());
+
+    let source: String = (0..70_000).map(|n| format!("print {};\n", n)).collect();
+    assert_eq!(lox.run("constants", &source), Err(LoxError::Compile));
+
+    let reports = reporter.reports();
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0].starts_with("error: Too many constants in one chunk.\n"));
+}
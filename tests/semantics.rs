@@ -0,0 +1,248 @@
+// Checks the truthiness and equality rules of Lox against both the
+// tree-walking interpreter and the bytecode virtual machine. Every
+// program is run through the lox binary, once with each backend.
+
+use std::{
+    env, fs,
+    process::{self, Command},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Numbers the scripts written by this process, so that tests running
+// at the same time, or another run of the suite, never share a file
+static SCRIPT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Runs the source with the given extra arguments, returning stdout
+fn run(name: &str, source: &str, args: &[&str]) -> String {
+    let count = SCRIPT_COUNT.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!(
+        "lox_semantics_{}_{}_{}.lox",
+        process::id(),
+        count,
+        name
+    ));
+    fs::write(&path, source).expect("Couldn't write the test script");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .expect("Couldn't run the interpreter");
+    let _ = fs::remove_file(&path);
+
+    assert!(
+        output.status.success(),
+        "{} failed with {:?}:\n{}",
+        name,
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("Output isn't valid UTF-8")
+}
+
+// Asserts that each backend prints the expected lines
+fn assert_prints(name: &str, source: &str, expected: &[&str]) {
+    let mut expected = expected.join("\n");
+    expected.push('\n');
+
+    assert_eq!(run(name, source, &[]), expected, "tree-walker: {}", name);
+    assert_eq!(
+        run(&format!("{}_vm", name), source, &["--vm"]),
+        expected,
+        "vm: {}",
+        name
+    );
+}
+
+#[test]
+fn nil_and_false_are_falsy() {
+    assert_prints(
+        "falsy",
+        r#"
+        if (nil) print "nil"; else print "not nil";
+        if (false) print "false"; else print "not false";
+        "#,
+        &["not nil", "not false"],
+    );
+}
+
+#[test]
+fn everything_else_is_truthy() {
+    assert_prints(
+        "truthy",
+        r#"
+        if (true) print "true";
+        if (0) print "0";
+        if (-1) print "-1";
+        if ("") print "empty string";
+        if ("false") print "string";
+        fun f() {}
+        if (f) print "function";
+        class A { m() {} }
+        if (A) print "class";
+        if (A()) print "instance";
+        if (A().m) print "method";
+        "#,
+        &[
+            "true",
+            "0",
+            "-1",
+            "empty string",
+            "string",
+            "function",
+            "class",
+            "instance",
+            "method",
+        ],
+    );
+}
+
+#[test]
+fn not_negates_any_value() {
+    assert_prints(
+        "not",
+        r#"
+        print !true;
+        print !false;
+        print !nil;
+        print !0;
+        print !"";
+        print !!nil;
+        print !!"a";
+        class A {}
+        print !A();
+        "#,
+        &[
+            "false", "true", "true", "false", "false", "false", "true", "false",
+        ],
+    );
+}
+
+#[test]
+fn not_leaves_its_operand_unchanged() {
+    assert_prints(
+        "not_operand",
+        r#"
+        var a = true;
+        print !a;
+        print a;
+        print !(1 == 2);
+        print !-1;
+        "#,
+        &["false", "true", "true", "false"],
+    );
+}
+
+#[test]
+fn nil_equals_only_nil() {
+    assert_prints(
+        "nil_equality",
+        r#"
+        print nil == nil;
+        print nil == false;
+        print false == nil;
+        print nil == 0;
+        print nil == "";
+        print nil != false;
+        "#,
+        &["true", "false", "false", "false", "false", "true"],
+    );
+}
+
+#[test]
+fn values_of_different_types_are_never_equal() {
+    assert_prints(
+        "mixed_equality",
+        r#"
+        print 1 == "1";
+        print "1" == 1;
+        print true == 1;
+        print false == 0;
+        print "true" == true;
+        print 1 != "1";
+        "#,
+        &["false", "false", "false", "false", "false", "true"],
+    );
+}
+
+#[test]
+fn numbers_strings_and_booleans_compare_by_value() {
+    assert_prints(
+        "value_equality",
+        r#"
+        print 1 == 1;
+        print 1 == 1.0;
+        print 0 == -0;
+        print 1 == 2;
+        print "abc" == "abc";
+        print "abc" == "abd";
+        print "a" + "bc" == "ab" + "c";
+        print true == true;
+        print true != false;
+        "#,
+        &[
+            "true", "true", "true", "false", "true", "false", "true", "true", "true",
+        ],
+    );
+}
+
+#[test]
+fn nan_is_not_equal_to_itself() {
+    assert_prints(
+        "nan",
+        r#"
+        var nan = 0 / 0;
+        print nan == nan;
+        print nan != nan;
+        "#,
+        &["false", "true"],
+    );
+}
+
+#[test]
+fn functions_classes_and_instances_compare_by_identity() {
+    assert_prints(
+        "identity_equality",
+        r#"
+        fun f() {}
+        fun g() {}
+        print f == f;
+        print f == g;
+        class A {}
+        class B {}
+        print A == A;
+        print A == B;
+        var a = A();
+        print a == a;
+        print a == A();
+        var same = a;
+        print same == a;
+        "#,
+        &["true", "false", "true", "false", "true", "false", "true"],
+    );
+}
+
+#[test]
+fn logical_operators_return_an_operand() {
+    assert_prints(
+        "logical",
+        r#"
+        print nil or "default";
+        print "value" or "default";
+        print false and "never";
+        print 1 and 2;
+        print nil and nil == false;
+        "#,
+        &["default", "value", "false", "2", "nil"],
+    );
+}
+
+#[test]
+fn long_scripts_reuse_the_constants_of_a_chunk() {
+    // Every line refers to the global and the literal again
+    let mut source = String::from("var x = 0;\n");
+    source.push_str(&"x = x + 1;\n".repeat(25_000));
+    source.push_str("print x;\n");
+
+    assert_prints("constants", &source, &["25000"]);
+}